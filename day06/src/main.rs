@@ -1,106 +1,250 @@
-use std::{collections::HashMap, io::Read};
+use std::io::Read;
 
-struct PacketStartIterator<I: Iterator<Item = u8>, const N: usize> {
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Marker {
+    /// Number of bytes read when the marker was complete.
     position: usize,
-    iter: I,
-    packet_buffer: [u8; N],
+    window: Vec<u8>,
 }
 
-impl<I: Iterator<Item = u8>, const N: usize> Iterator for PacketStartIterator<I, N> {
-    type Item = usize;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        'outer: loop {
-            let v = match self.iter.next() {
-                None => return None,
-                Some(v) => v,
-            };
-            self.packet_buffer[self.position % self.packet_buffer.len()] = v;
-            self.position += 1;
-            if self.position < self.packet_buffer.len() {
-                continue;
-            }
-            for i in 0..(self.packet_buffer.len() - 1) {
-                for j in (i + 1)..self.packet_buffer.len() {
-                    if self.packet_buffer[i] == self.packet_buffer[j] {
-                        continue 'outer;
-                    }
-                }
-            }
-            return Some(self.position);
-        }
-    }
+struct MarkerDetector<I: Iterator<Item = u8>> {
+    position: usize,
+    iter: I,
+    window: Vec<u8>,
+    byte_counts: [u16; 256],
+    different_byte_count: usize,
 }
 
-impl<I: Iterator<Item = u8>, const N: usize> From<I> for PacketStartIterator<I, N> {
-    fn from(it: I) -> Self {
+impl<I: Iterator<Item = u8>> MarkerDetector<I> {
+    fn new(iter: I, window_size: usize) -> Self {
+        assert!(window_size > 0, "Window size must be positive");
+        assert!(
+            window_size <= u16::MAX as usize,
+            "Window size {} too large",
+            window_size
+        );
         Self {
-            iter: it,
             position: 0,
-            packet_buffer: [0; N],
+            iter,
+            window: vec![0; window_size],
+            byte_counts: [0; 256],
+            different_byte_count: 0,
         }
     }
-}
 
-struct MessageStartIterator<I: Iterator<Item = u8>, const N: usize> {
-    position: usize,
-    iter: I,
-    packet_buffer: [u8; N],
-    byte_counts: HashMap<u8, u8>,
-    different_byte_count: u8,
+    fn window_contents(&self) -> Vec<u8> {
+        let split = self.position % self.window.len();
+        let mut contents = Vec::with_capacity(self.window.len());
+        contents.extend_from_slice(&self.window[split..]);
+        contents.extend_from_slice(&self.window[..split]);
+        contents
+    }
 }
 
-impl<I: Iterator<Item = u8>, const N: usize> Iterator for MessageStartIterator<I, N> {
-    type Item = usize;
+impl<I: Iterator<Item = u8>> Iterator for MarkerDetector<I> {
+    type Item = Marker;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let v = match self.iter.next() {
-                None => return None,
-                Some(v) => v,
-            };
-            let entry = self.byte_counts.entry(v).or_default();
-            *entry += 1u8;
-            if *entry == 1 {
+            let v = self.iter.next()?;
+            let slot = self.position % self.window.len();
+            if self.position >= self.window.len() {
+                let previous_count = &mut self.byte_counts[self.window[slot] as usize];
+                *previous_count -= 1;
+                if *previous_count == 0 {
+                    self.different_byte_count -= 1;
+                }
+            }
+            let count = &mut self.byte_counts[v as usize];
+            *count += 1;
+            if *count == 1 {
                 self.different_byte_count += 1;
             }
-            let previous_value = self.packet_buffer[self.position % self.packet_buffer.len()];
-            self.packet_buffer[self.position % self.packet_buffer.len()] = v;
+            self.window[slot] = v;
             self.position += 1;
-            if self.position < self.packet_buffer.len() + 1 {
-                continue;
+            if self.different_byte_count == self.window.len() {
+                return Some(Marker {
+                    position: self.position,
+                    window: self.window_contents(),
+                });
             }
-            let previous_count = self.byte_counts.get_mut(&previous_value).unwrap();
-            if *previous_count == 1 {
-                self.different_byte_count -= 1;
+        }
+    }
+}
+
+/// Bitmask fast path over a slice whose bytes all fit in a 64-value range (such as lowercase
+/// letters): each byte toggles its bit in a `u64` as it enters and leaves the window. A window of
+/// N bytes with N bits set has N bytes with an odd count, so every byte appears exactly once.
+struct BitmaskMarkers<'a> {
+    data: &'a [u8],
+    window_size: usize,
+    base: u8,
+    index: usize,
+    mask: u64,
+}
+
+impl<'a> BitmaskMarkers<'a> {
+    /// Returns `None` if the bytes of `data` span more than 64 values.
+    fn new(data: &'a [u8], window_size: usize) -> Option<Self> {
+        assert!(window_size > 0, "Window size must be positive");
+        let base = data.iter().copied().min().unwrap_or(0);
+        let max = data.iter().copied().max().unwrap_or(0);
+        if max - base >= 64 {
+            return None;
+        }
+        Some(Self {
+            data,
+            window_size,
+            base,
+            index: 0,
+            mask: 0,
+        })
+    }
+}
+
+impl Iterator for BitmaskMarkers<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Work on a local copy so that the mask stays in a register.
+        let mut mask = self.mask;
+        let mut found = None;
+        while self.index < self.data.len() {
+            mask ^= 1 << (self.data[self.index] - self.base);
+            if self.index >= self.window_size {
+                mask ^= 1 << (self.data[self.index - self.window_size] - self.base);
             }
-            *previous_count -= 1;
-            /*
-            assert_eq!(
-                self.byte_counts.values().sum::<u8>() as usize,
-                self.packet_buffer.len()
-            );
-            */
-            if self.different_byte_count == self.packet_buffer.len() as u8 {
-                return Some(self.position);
+            self.index += 1;
+            if mask.count_ones() as usize == self.window_size {
+                found = Some(self.index);
+                break;
             }
         }
+        self.mask = mask;
+        found
     }
 }
 
-impl<I: Iterator<Item = u8>, const N: usize> From<I> for MessageStartIterator<I, N> {
-    fn from(it: I) -> Self {
-        Self {
-            iter: it,
-            position: 0,
-            packet_buffer: [0; N],
-            byte_counts: HashMap::new(),
-            different_byte_count: 0,
+/// Positions of all the markers in `data`, using the bitmask fast path when possible.
+fn slice_markers(data: &[u8], window_size: usize) -> Box<dyn Iterator<Item = usize> + '_> {
+    match BitmaskMarkers::new(data, window_size) {
+        Some(markers) => Box::new(markers),
+        None => {
+            Box::new(MarkerDetector::new(data.iter().copied(), window_size).map(|m| m.position))
+        }
+    }
+}
+
+/// Scans the reader chunk by chunk, calling `on_marker(window_size, position)` for every marker
+/// of each window size as soon as it is found, in stream order. Only the last `max(window_sizes) - 1` bytes of a
+/// chunk are carried over to the next one, so memory use does not depend on the stream length.
+fn stream_markers<R: Read>(
+    mut reader: R,
+    window_sizes: &[usize],
+    mut on_marker: impl FnMut(usize, usize),
+) -> std::io::Result<()> {
+    let carry_size = window_sizes.iter().max().map_or(0, |m| m - 1);
+    let mut buffer = vec![0; carry_size + CHUNK_SIZE];
+    // Number of bytes at the start of the buffer already scanned in a previous chunk.
+    let mut carried = 0;
+    // Stream offset of the start of the buffer.
+    let mut offset = 0;
+    loop {
+        let read = match reader.read(&mut buffer[carried..]) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let filled = carried + read;
+        {
+            let mut markers = window_sizes
+                .iter()
+                .map(|&window_size| {
+                    let markers = slice_markers(&buffer[..filled], window_size);
+                    (window_size, markers.filter(|&end| end > carried).peekable())
+                })
+                .collect::<Vec<_>>();
+            // Merge the markers of all the window sizes, so that they come out in stream order.
+            while let Some((_, window_size, markers)) = markers
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(i, (window_size, markers))| {
+                    Some(((*markers.peek()?, i), *window_size, markers))
+                })
+                .min_by_key(|&(key, _, _)| key)
+            {
+                on_marker(window_size, offset + markers.next().unwrap());
+            }
         }
+        let keep = carry_size.min(filled);
+        buffer.copy_within(filled - keep..filled, 0);
+        offset += filled - keep;
+        carried = keep;
     }
 }
 
+fn bench() {
+    const BENCH_SIZE: usize = 64 * 1024 * 1024;
+    // Xorshift over a small alphabet, so that markers are neither everywhere nor nowhere.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let data: Vec<u8> = (0..BENCH_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            b'a' + (state % 26) as u8
+        })
+        .collect();
+    for window_size in [4, 14] {
+        let start = std::time::Instant::now();
+        let detector_count = MarkerDetector::new(data.iter().copied(), window_size).count();
+        let detector_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let bitmask_count = slice_markers(&data, window_size).count();
+        let bitmask_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let mut stream_count = 0;
+        stream_markers(data.as_slice(), &[window_size], |_, _| stream_count += 1)
+            .expect("Error reading bench data");
+        let stream_time = start.elapsed();
+        assert_eq!(detector_count, bitmask_count);
+        assert_eq!(detector_count, stream_count);
+        println!(
+            "Window {}: {} markers, detector {:?}, bitmask {:?}, stream {:?}",
+            window_size, detector_count, detector_time, bitmask_time, stream_time
+        );
+    }
+}
+
+fn stream() {
+    use std::io::Write;
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    stream_markers(
+        std::io::stdin().lock(),
+        &[4, 14],
+        |window_size, position| {
+            let kind = if window_size == 4 {
+                "Packet"
+            } else {
+                "Message"
+            };
+            writeln!(out, "{} start: {}", kind, position).expect("Error writing stdout");
+        },
+    )
+    .expect("Error reading stdin");
+}
+
 fn main() {
+    match std::env::args().nth(1).as_deref() {
+        Some("stream") => return stream(),
+        Some("bench") => return bench(),
+        Some(mode) => panic!("Unknown mode: {}", mode),
+        None => (),
+    }
     let contents = {
         let mut buf = String::new();
         std::io::stdin()
@@ -110,14 +254,103 @@ fn main() {
     };
     println!(
         "First packet start: {}",
-        Into::<PacketStartIterator<_, 4>>::into(contents.bytes())
+        MarkerDetector::new(contents.bytes(), 4)
             .next()
             .expect("No packet start detected")
+            .position
     );
     println!(
         "First message start: {}",
-        Into::<MessageStartIterator<_, 14>>::into(contents.bytes())
+        MarkerDetector::new(contents.bytes(), 14)
             .next()
             .expect("No message start detected")
+            .position
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLES: [(&str, usize, usize); 4] = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+        ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+        ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+    ];
+
+    #[test]
+    fn test_examples() {
+        for (input, packet, message) in EXAMPLES {
+            let first = |window_size| {
+                MarkerDetector::new(input.bytes(), window_size)
+                    .next()
+                    .map(|m| m.position)
+            };
+            assert_eq!(first(4), Some(packet));
+            assert_eq!(first(14), Some(message));
+            assert_eq!(slice_markers(input.as_bytes(), 4).next(), Some(packet));
+            assert_eq!(slice_markers(input.as_bytes(), 14).next(), Some(message));
+        }
+        let marker = MarkerDetector::new(EXAMPLES[0].0.bytes(), 4)
+            .next()
+            .unwrap();
+        assert_eq!(marker.window, b"jpqm");
+    }
+
+    /// Returns at most `max_read` bytes per read, and is interrupted before every other read.
+    struct SmallReads<'a> {
+        data: &'a [u8],
+        max_read: usize,
+        interrupt: bool,
+    }
+
+    impl Read for SmallReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            let len = self.max_read.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_stream_across_small_reads() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let data: Vec<u8> = (0..5000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                b'a' + (state % 20) as u8
+            })
+            .collect();
+        let expected = [4, 14]
+            .into_iter()
+            .flat_map(|window_size| {
+                MarkerDetector::new(data.iter().copied(), window_size)
+                    .map(move |m| (window_size, m.position))
+            })
+            .collect::<Vec<_>>();
+        let mut expected = expected;
+        expected.sort_by_key(|&(window_size, position)| (position, window_size));
+        assert!(expected.iter().any(|&(window_size, _)| window_size == 14));
+        for max_read in [1, 3, 13, 14, 100, 5000] {
+            let reader = SmallReads {
+                data: &data,
+                max_read,
+                interrupt: false,
+            };
+            let mut found = Vec::new();
+            stream_markers(reader, &[4, 14], |window_size, position| {
+                found.push((window_size, position))
+            })
+            .unwrap();
+            assert_eq!(found, expected, "Reads of {} bytes", max_read);
+        }
+    }
+}
@@ -1,16 +1,23 @@
+mod query;
+
+use std::collections::BTreeMap;
+
+use crate::query::{run_query, QueryParams, QueryResult};
+
 type Error = &'static str;
 type Result<T> = std::result::Result<T, Error>;
 
 enum TargetDirectory {
+    Root,
     Up,
-    Down,
+    Down(String),
 }
 
 enum Instruction {
     Cd(TargetDirectory),
     Ls,
-    FileListing(u64),
-    DirListing,
+    FileListing { name: String, size: u64 },
+    DirListing(String),
 }
 
 fn line_to_instruction<S: AsRef<str>>(input: S) -> Result<Instruction> {
@@ -20,134 +27,449 @@ fn line_to_instruction<S: AsRef<str>>(input: S) -> Result<Instruction> {
             Ok(Instruction::Ls)
         } else if let Some(target) = command.strip_prefix("cd ") {
             Ok(Instruction::Cd(if target == "/" {
-                return Err("cd / not supported");
+                TargetDirectory::Root
             } else if target == ".." {
                 TargetDirectory::Up
             } else {
-                TargetDirectory::Down
+                TargetDirectory::Down(target.to_owned())
             }))
         } else {
             Err("Unrecognized command")
         }
-    } else if input.starts_with("dir ") {
-        Ok(Instruction::DirListing)
-    } else if let Some((size, _)) = input.split_once(' ') {
-        Ok(Instruction::FileListing(
-            size.parse::<u64>().map_err(|_| "Invalid number")?,
-        ))
+    } else if let Some(name) = input.strip_prefix("dir ") {
+        Ok(Instruction::DirListing(name.to_owned()))
+    } else if let Some((size, name)) = input.split_once(' ') {
+        Ok(Instruction::FileListing {
+            name: name.to_owned(),
+            size: size.parse::<u64>().map_err(|_| "Invalid number")?,
+        })
     } else {
         Err("Unrecognized line")
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
 struct File {
+    name: String,
     size: u64,
 }
 
+#[derive(Debug, PartialEq, Eq)]
 struct Directory {
+    name: String,
+    files: Vec<File>,
     dirs: Vec<Directory>,
     size: u64,
 }
 
+/// Directory contents as seen so far. Entries are keyed by name so that listing the same
+/// directory twice doesn't count its files twice.
 #[derive(Default)]
-struct DirectoryBuilder {
-    current_files: Vec<File>,
-    current_dirs: Vec<Directory>,
-    current_builder: Box<Option<DirectoryBuilder>>,
+struct DirectoryNode {
+    files: BTreeMap<String, u64>,
+    dirs: BTreeMap<String, DirectoryNode>,
 }
 
-enum ApplyResult {
-    Applied(DirectoryBuilder),
-    Finished(Directory),
+impl DirectoryNode {
+    fn finish(self, name: String) -> Directory {
+        let files = self
+            .files
+            .into_iter()
+            .map(|(name, size)| File { name, size })
+            .collect::<Vec<_>>();
+        let dirs = self
+            .dirs
+            .into_iter()
+            .map(|(name, dir)| dir.finish(name))
+            .collect::<Vec<_>>();
+        let size =
+            files.iter().map(|f| f.size).sum::<u64>() + dirs.iter().map(|d| d.size).sum::<u64>();
+        Directory {
+            name,
+            files,
+            dirs,
+            size,
+        }
+    }
+}
+
+#[derive(Default)]
+struct DirectoryBuilder {
+    root: DirectoryNode,
+    current_path: Vec<String>,
 }
 
 impl DirectoryBuilder {
-    fn finish(mut self) -> Directory {
-        if let Some(dir) = self.current_builder.take() {
-            let last_dir = dir.finish();
-            self.current_dirs.push(last_dir);
-        }
-        let size = self.current_files.iter().map(|f| f.size).sum::<u64>()
-            + self.current_dirs.iter().map(|d| d.size).sum::<u64>();
-        Directory {
-            dirs: self.current_dirs,
-            size,
+    fn finish(self) -> Directory {
+        self.root.finish("/".to_owned())
+    }
+
+    /// The current directory, created along with its parents if they were never listed.
+    fn current_dir(&mut self) -> &mut DirectoryNode {
+        self.current_path.iter().fold(&mut self.root, |dir, name| {
+            dir.dirs.entry(name.clone()).or_default()
+        })
+    }
+
+    fn apply(mut self, instruction: Instruction) -> Self {
+        match instruction {
+            Instruction::Cd(TargetDirectory::Root) => self.current_path.clear(),
+            // Like a shell, "cd .." from the root stays at the root.
+            Instruction::Cd(TargetDirectory::Up) => {
+                self.current_path.pop();
+            }
+            Instruction::Cd(TargetDirectory::Down(name)) => {
+                self.current_dir().dirs.entry(name.clone()).or_default();
+                self.current_path.push(name);
+            }
+            Instruction::Ls => (),
+            Instruction::DirListing(name) => {
+                self.current_dir().dirs.entry(name).or_default();
+            }
+            Instruction::FileListing { name, size } => {
+                self.current_dir().files.insert(name, size);
+            }
         }
+        self
     }
-    fn apply_top(self, instruction: Instruction) -> Self {
-        match self.apply(instruction) {
-            ApplyResult::Applied(s) => s,
-            ApplyResult::Finished(_) => unreachable!(),
+}
+
+impl Directory {
+    /// Calls `f` on this directory and all its subdirectories, with their absolute paths.
+    fn for_each_dir<'a>(&'a self, path: &str, f: &mut impl FnMut(&str, &'a Directory)) {
+        f(path, self);
+        for d in &self.dirs {
+            d.for_each_dir(&join_path(path, &d.name), f);
         }
     }
-    fn apply(mut self, instruction: Instruction) -> ApplyResult {
-        if self.current_builder.is_some() {
-            let maybe_builder = std::mem::replace(&mut *self.current_builder, None);
-            *self.current_builder = if let Some(builder) = maybe_builder {
-                match builder.apply(instruction) {
-                    ApplyResult::Applied(builder) => Some(builder),
-                    ApplyResult::Finished(dir) => {
-                        self.current_dirs.push(dir);
-                        None
-                    }
-                }
+
+    /// All the files in the tree, with their absolute paths.
+    fn all_files(&self) -> Vec<(String, &File)> {
+        let mut files = Vec::new();
+        self.for_each_dir("/", &mut |path, d| {
+            files.extend(d.files.iter().map(|f| (join_path(path, &f.name), f)))
+        });
+        files
+    }
+
+    /// All the directories in the tree, including this one, with their absolute paths.
+    fn all_dirs(&self) -> Vec<(String, &Directory)> {
+        let mut dirs = Vec::new();
+        self.for_each_dir("/", &mut |path, d| dirs.push((path.to_owned(), d)));
+        dirs
+    }
+}
+
+fn join_path(parent: &str, name: &str) -> String {
+    if parent.ends_with('/') {
+        format!("{}{}", parent, name)
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// Size with a binary unit suffix, the way `du -h` prints it: "584", "4.0K", "14M".
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["", "K", "M", "G", "T"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}", size)
+    } else if value < 10.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+/// `tree`-like view of a directory, with sizes.
+struct TreeView<'a>(&'a Directory);
+
+impl TreeView<'_> {
+    fn fmt_entries(
+        dir: &Directory,
+        prefix: &str,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        // Entries are interleaved by name, like `tree` does.
+        let mut entries = dir
+            .dirs
+            .iter()
+            .map(|d| (d.name.as_str(), d.size, Some(d)))
+            .chain(dir.files.iter().map(|f| (f.name.as_str(), f.size, None)))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|e| e.0);
+        for (i, (name, size, subdir)) in entries.iter().enumerate() {
+            let last = i + 1 == entries.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
             } else {
-                unreachable!()
+                ("├── ", "│   ")
             };
-        } else {
-            match instruction {
-                Instruction::Cd(TargetDirectory::Up) => {
-                    return ApplyResult::Finished(self.finish())
-                }
-                Instruction::Cd(TargetDirectory::Down) => {
-                    assert!(self.current_builder.is_none());
-                    *self.current_builder = Some(DirectoryBuilder::default());
-                }
-                Instruction::Ls | Instruction::DirListing => (),
-                Instruction::FileListing(size) => self.current_files.push(File { size }),
+            let slash = if subdir.is_some() { "/" } else { "" };
+            writeln!(f, "{}{}{}{} ({})", prefix, branch, name, slash, size)?;
+            if let Some(subdir) = subdir {
+                Self::fmt_entries(subdir, &format!("{}{}", prefix, indent), f)?;
             }
         }
-        ApplyResult::Applied(self)
+        Ok(())
     }
 }
 
-fn compute_sum_of_sizes(dir: &Directory, max_size: u64) -> u64 {
-    dir.dirs
-        .iter()
-        .map(|d| compute_sum_of_sizes(d, max_size))
-        .sum::<u64>()
-        + if dir.size < max_size { dir.size } else { 0 }
+impl std::fmt::Display for TreeView<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} ({})", self.0.name, self.0.size)?;
+        Self::fmt_entries(self.0, "", f)
+    }
 }
 
-fn find_smallest_dir_above(min_size: u64, dir: &Directory) -> u64 {
-    let best_subdir = dir
-        .dirs
-        .iter()
-        .filter(|d| d.size >= min_size) // short-circuit
-        .map(|d| find_smallest_dir_above(min_size, d))
-        .min();
-    assert!(dir.size >= min_size);
-    if let Some(s) = best_subdir {
-        if dir.size >= s {
-            return s;
+struct JsonView<'a>(&'a Directory);
+
+fn write_json_string(s: &str, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl std::fmt::Display for JsonView<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{\"name\":")?;
+        write_json_string(&self.0.name, f)?;
+        write!(f, ",\"size\":{},\"files\":[", self.0.size)?;
+        for (i, file) in self.0.files.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{{\"name\":")?;
+            write_json_string(&file.name, f)?;
+            write!(f, ",\"size\":{}}}", file.size)?;
         }
+        write!(f, "],\"dirs\":[")?;
+        for (i, dir) in self.0.dirs.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", JsonView(dir))?;
+        }
+        write!(f, "]}}")
+    }
+}
+
+/// All the directories, largest first.
+fn dirs_by_size(root: &Directory) -> Vec<(String, &Directory)> {
+    let mut dirs = root.all_dirs();
+    dirs.sort_by(|(p1, d1), (p2, d2)| d2.size.cmp(&d1.size).then_with(|| p1.cmp(p2)));
+    dirs
+}
+
+fn print_du(root: &Directory) {
+    let dirs = dirs_by_size(root);
+    for (path, dir) in dirs {
+        println!("{}\t{}", human_size(dir.size), path);
+    }
+}
+
+fn print_largest(root: &Directory, count: usize) {
+    let mut files = root.all_files();
+    files.sort_by(|(p1, f1), (p2, f2)| f2.size.cmp(&f1.size).then_with(|| p1.cmp(p2)));
+    println!("Largest files:");
+    for (path, file) in files.iter().take(count) {
+        println!("{}\t{}", file.size, path);
+    }
+    let dirs = dirs_by_size(root);
+    println!("Largest directories:");
+    for (path, dir) in dirs.iter().take(count) {
+        println!("{}\t{}", dir.size, path);
     }
-    dir.size
 }
 
 fn main() {
     let root = std::io::stdin()
         .lines()
         .map(std::result::Result::unwrap)
-        .skip(1) // Skip "$ cd /"
         .map(line_to_instruction)
         .map(std::result::Result::unwrap)
-        .fold(DirectoryBuilder::default(), DirectoryBuilder::apply_top)
+        .fold(DirectoryBuilder::default(), DirectoryBuilder::apply)
         .finish();
-    let sum = compute_sum_of_sizes(&root, 100000);
-    println!("{}", sum);
-    let min_size = 30_000_000 - (70_000_000 - root.size);
-    assert!(min_size < root.size);
-    let smallest_dir = find_smallest_dir_above(min_size, &root);
-    println!("{}", smallest_dir);
+    let mut params = QueryParams::default();
+    let mut args = Vec::new();
+    let mut raw_args = std::env::args().skip(1);
+    while let Some(arg) = raw_args.next() {
+        let mut value = || -> u64 {
+            raw_args
+                .next()
+                .and_then(|v| v.parse().ok())
+                .expect("Expected a size after option")
+        };
+        match arg.as_str() {
+            "--disk" => params.disk_size = value(),
+            "--required" => params.required_free = value(),
+            _ => args.push(arg),
+        }
+    }
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => (),
+        ["tree"] => return print!("{}", TreeView(&root)),
+        ["du"] => return print_du(&root),
+        ["largest", count] => {
+            return print_largest(&root, count.parse().expect("Invalid count"));
+        }
+        ["json"] => return println!("{}", JsonView(&root)),
+        ["query", query] => {
+            match run_query(query, &root, params).unwrap() {
+                QueryResult::Value(v) => println!("{}", v),
+                QueryResult::Entries(entries) => {
+                    for (path, size) in entries {
+                        println!("{}\t{}", size, path);
+                    }
+                }
+            }
+            return;
+        }
+        _ => panic!(
+            "Usage: day07 [tree | du | largest <count> | json | query <query>] \
+             [--disk <size>] [--required <size>]"
+        ),
+    }
+    for query in [
+        "dirs where size < 100000 | sum",
+        "dirs where size >= needed | min",
+    ] {
+        match run_query(query, &root, params).unwrap() {
+            QueryResult::Value(v) => println!("{}", v),
+            QueryResult::Entries(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n$ cd e\n$ ls\n584 i\n$ cd ..\n$ cd ..\n$ cd d\n$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k";
+
+    fn replay(transcript: &str) -> Directory {
+        transcript
+            .lines()
+            .map(line_to_instruction)
+            .map(std::result::Result::unwrap)
+            .fold(DirectoryBuilder::default(), DirectoryBuilder::apply)
+            .finish()
+    }
+
+    #[test]
+    fn test_replay_real_session() {
+        let root = replay(
+            "$ cd a\n$ ls\n10 f\n$ cd /\n$ ls\ndir a\n5 g\n$ ls\ndir a\n5 g\n$ cd a\n$ ls\n10 f",
+        );
+        assert_eq!(root.size, 15);
+        assert_eq!(
+            root.files,
+            vec![File {
+                name: "g".to_owned(),
+                size: 5
+            }]
+        );
+        assert_eq!(root.dirs.len(), 1);
+        assert_eq!(root.dirs[0].name, "a");
+        assert_eq!(root.dirs[0].size, 10);
+    }
+
+    #[test]
+    fn test_queries() {
+        let root = replay(EXAMPLE);
+        let params = QueryParams::default();
+        assert_eq!(
+            run_query("dirs where size < 100000 | sum", &root, params),
+            Ok(QueryResult::Value(95437))
+        );
+        assert_eq!(
+            run_query("dirs where size >= needed | min size", &root, params),
+            Ok(QueryResult::Value(24933642))
+        );
+        // 21618835 bytes are free out of the 30000000 required.
+        assert_eq!(
+            run_query("dirs where size < free | count", &root, params),
+            Ok(QueryResult::Value(2))
+        );
+        assert_eq!(
+            run_query("dirs where size >= required | count", &root, params),
+            Ok(QueryResult::Value(1))
+        );
+        assert_eq!(
+            run_query("files where depth>1 and size<30000", &root, params),
+            Ok(QueryResult::Entries(vec![
+                ("/a/f".to_owned(), 29116),
+                ("/a/g".to_owned(), 2557),
+                ("/a/e/i".to_owned(), 584)
+            ]))
+        );
+        assert!(run_query("dirs | count size", &root, params).is_err());
+        assert!(run_query("dirs | count depth", &root, params).is_err());
+        assert_eq!(
+            run_query("files | max depth", &root, params),
+            Ok(QueryResult::Value(3))
+        );
+    }
+
+    #[test]
+    fn test_reports() {
+        let root = replay(EXAMPLE);
+        assert_eq!(
+            TreeView(&root).to_string(),
+            "/ (48381165)\n\
+             ├── a/ (94853)\n\
+             │   ├── e/ (584)\n\
+             │   │   └── i (584)\n\
+             │   ├── f (29116)\n\
+             │   ├── g (2557)\n\
+             │   └── h.lst (62596)\n\
+             ├── b.txt (14848514)\n\
+             ├── c.dat (8504156)\n\
+             └── d/ (24933642)\n    \
+             ├── d.ext (5626152)\n    \
+             ├── d.log (8033020)\n    \
+             ├── j (4060174)\n    \
+             └── k (7214296)\n"
+        );
+        assert_eq!(
+            JsonView(&root.dirs[0]).to_string(),
+            "{\"name\":\"a\",\"size\":94853,\"files\":[{\"name\":\"f\",\"size\":29116},\
+             {\"name\":\"g\",\"size\":2557},{\"name\":\"h.lst\",\"size\":62596}],\
+             \"dirs\":[{\"name\":\"e\",\"size\":584,\"files\":[{\"name\":\"i\",\"size\":584}],\
+             \"dirs\":[]}]}"
+        );
+        assert_eq!(
+            JsonView(&replay("$ ls\n1 a\"b\\c\td")).to_string(),
+            "{\"name\":\"/\",\"size\":1,\"files\":[{\"name\":\"a\\\"b\\\\c\\u0009d\",\"size\":1}],\
+             \"dirs\":[]}"
+        );
+        assert_eq!(
+            dirs_by_size(&root)
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>(),
+            ["/", "/d", "/a", "/a/e"]
+        );
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(10 * 1024), "10K");
+        assert_eq!(human_size(root.size), "46M");
+    }
 }
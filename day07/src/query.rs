@@ -0,0 +1,325 @@
+//! Small query language over the directory tree:
+//!
+//! ```text
+//! dirs where size < 100000 | sum
+//! dirs where size >= needed | min size
+//! files where depth > 2 and size > 1000
+//! ```
+//!
+//! A query selects `dirs` or `files`, optionally filters them with conditions joined by `and`,
+//! and optionally aggregates them with `sum`, `min`, `max` (over `size` by default) or `count`.
+//! Without an aggregate, the matching entries are listed.
+
+use crate::{Directory, Result};
+
+/// Disk geometry, exposed to queries as the `disk` and `required` values. Queries also see the
+/// `used` and `free` space, and the space `needed` to get the required free space.
+#[derive(Clone, Copy)]
+pub struct QueryParams {
+    pub disk_size: u64,
+    pub required_free: u64,
+}
+
+impl Default for QueryParams {
+    fn default() -> Self {
+        Self {
+            disk_size: 70_000_000,
+            required_free: 30_000_000,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum QueryResult {
+    Value(u64),
+    Entries(Vec<(String, u64)>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Number(u64),
+    Op(Comparison),
+    Pipe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Comparison {
+    fn matches(self, left: u64, right: u64) -> bool {
+        match self {
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Source {
+    Dirs,
+    Files,
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Size,
+    Depth,
+}
+
+enum Value {
+    Number(u64),
+    Disk,
+    Required,
+    Free,
+    Used,
+    Needed,
+}
+
+struct Condition {
+    field: Field,
+    comparison: Comparison,
+    value: Value,
+}
+
+enum Aggregate {
+    Sum(Field),
+    Min(Field),
+    Max(Field),
+    Count,
+}
+
+struct Query {
+    source: Source,
+    conditions: Vec<Condition>,
+    aggregate: Option<Aggregate>,
+}
+
+/// An entry selected by the query source.
+struct Entry {
+    path: String,
+    size: u64,
+    depth: u64,
+}
+
+impl Entry {
+    fn get(&self, field: Field) -> u64 {
+        match field {
+            Field::Size => self.size,
+            Field::Depth => self.depth,
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if c == b'|' {
+            tokens.push(Token::Pipe);
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+                i += 1;
+            }
+            let number = input[start..i].replace('_', "");
+            tokens.push(Token::Number(
+                number.parse().map_err(|_| "Number too large")?,
+            ));
+        } else if c.is_ascii_alphabetic() {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push(Token::Word(input[start..i].to_owned()));
+        } else {
+            let next = bytes.get(i + 1).copied();
+            let (op, len) = match (c, next) {
+                (b'<', Some(b'=')) => (Comparison::Le, 2),
+                (b'<', _) => (Comparison::Lt, 1),
+                (b'>', Some(b'=')) => (Comparison::Ge, 2),
+                (b'>', _) => (Comparison::Gt, 1),
+                (b'=', Some(b'=')) => (Comparison::Eq, 2),
+                (b'=', _) => (Comparison::Eq, 1),
+                (b'!', Some(b'=')) => (Comparison::Ne, 2),
+                _ => return Err("Unexpected character in query"),
+            };
+            tokens.push(Token::Op(op));
+            i += len;
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_field(word: &str) -> Result<Field> {
+    match word {
+        "size" => Ok(Field::Size),
+        "depth" => Ok(Field::Depth),
+        _ => Err("Unknown field"),
+    }
+}
+
+fn parse_value(token: Option<Token>) -> Result<Value> {
+    match token {
+        Some(Token::Number(n)) => Ok(Value::Number(n)),
+        Some(Token::Word(w)) => match w.as_str() {
+            "disk" => Ok(Value::Disk),
+            "required" => Ok(Value::Required),
+            "free" => Ok(Value::Free),
+            "used" => Ok(Value::Used),
+            "needed" => Ok(Value::Needed),
+            _ => Err("Unknown parameter"),
+        },
+        _ => Err("Expected a value"),
+    }
+}
+
+fn parse_query(input: &str) -> Result<Query> {
+    let mut tokens = tokenize(input)?.into_iter().peekable();
+    let source = match tokens.next() {
+        Some(Token::Word(w)) if w == "dirs" => Source::Dirs,
+        Some(Token::Word(w)) if w == "files" => Source::Files,
+        _ => return Err("Query must start with \"dirs\" or \"files\""),
+    };
+    let mut conditions = Vec::new();
+    if tokens
+        .next_if_eq(&Token::Word("where".to_owned()))
+        .is_some()
+    {
+        loop {
+            let field = match tokens.next() {
+                Some(Token::Word(w)) => parse_field(&w)?,
+                _ => return Err("Expected a field"),
+            };
+            let comparison = match tokens.next() {
+                Some(Token::Op(op)) => op,
+                _ => return Err("Expected a comparison"),
+            };
+            let value = parse_value(tokens.next())?;
+            conditions.push(Condition {
+                field,
+                comparison,
+                value,
+            });
+            if tokens.next_if_eq(&Token::Word("and".to_owned())).is_none() {
+                break;
+            }
+        }
+    }
+    let aggregate = match tokens.next() {
+        None => None,
+        Some(Token::Pipe) => {
+            let name = match tokens.next() {
+                Some(Token::Word(w)) => w,
+                _ => return Err("Expected an aggregate"),
+            };
+            Some(match name.as_str() {
+                // Only the other aggregates are over a field.
+                "count" => match tokens.peek() {
+                    None => Aggregate::Count,
+                    Some(_) => return Err("\"count\" takes no field"),
+                },
+                "sum" | "min" | "max" => {
+                    let field = match tokens.next() {
+                        Some(Token::Word(w)) => parse_field(&w)?,
+                        None => Field::Size,
+                        _ => return Err("Expected a field"),
+                    };
+                    match name.as_str() {
+                        "sum" => Aggregate::Sum(field),
+                        "min" => Aggregate::Min(field),
+                        _ => Aggregate::Max(field),
+                    }
+                }
+                _ => return Err("Unknown aggregate"),
+            })
+        }
+        Some(_) => return Err("Expected \"where\" or \"|\""),
+    };
+    if tokens.next().is_some() {
+        return Err("Unexpected token at end of query");
+    }
+    Ok(Query {
+        source,
+        conditions,
+        aggregate,
+    })
+}
+
+fn collect_entries(dir: &Directory, path: &str, depth: u64, source: Source, out: &mut Vec<Entry>) {
+    match source {
+        Source::Dirs => out.push(Entry {
+            path: path.to_owned(),
+            size: dir.size,
+            depth,
+        }),
+        Source::Files => out.extend(dir.files.iter().map(|f| Entry {
+            path: crate::join_path(path, &f.name),
+            size: f.size,
+            depth: depth + 1,
+        })),
+    }
+    for d in &dir.dirs {
+        collect_entries(d, &crate::join_path(path, &d.name), depth + 1, source, out);
+    }
+}
+
+/// Parses and evaluates the query over the tree rooted at `root`.
+pub fn run_query(input: &str, root: &Directory, params: QueryParams) -> Result<QueryResult> {
+    let query = parse_query(input)?;
+    let resolve = |value: &Value| match *value {
+        Value::Number(n) => n,
+        Value::Disk => params.disk_size,
+        Value::Required => params.required_free,
+        Value::Free => params.disk_size.saturating_sub(root.size),
+        Value::Used => root.size,
+        Value::Needed => params
+            .required_free
+            .saturating_sub(params.disk_size.saturating_sub(root.size)),
+    };
+    let mut entries = Vec::new();
+    collect_entries(root, "/", 0, query.source, &mut entries);
+    entries.retain(|e| {
+        query
+            .conditions
+            .iter()
+            .all(|c| c.comparison.matches(e.get(c.field), resolve(&c.value)))
+    });
+    Ok(match query.aggregate {
+        None => QueryResult::Entries(entries.into_iter().map(|e| (e.path, e.size)).collect()),
+        Some(Aggregate::Count) => QueryResult::Value(entries.len() as u64),
+        Some(Aggregate::Sum(field)) => {
+            QueryResult::Value(entries.iter().map(|e| e.get(field)).sum())
+        }
+        Some(Aggregate::Min(field)) => QueryResult::Value(
+            entries
+                .iter()
+                .map(|e| e.get(field))
+                .min()
+                .ok_or("No matching entries")?,
+        ),
+        Some(Aggregate::Max(field)) => QueryResult::Value(
+            entries
+                .iter()
+                .map(|e| e.get(field))
+                .max()
+                .ok_or("No matching entries")?,
+        ),
+    })
+}
@@ -1,10 +1,14 @@
-struct Tree(u8);
+type Error = &'static str;
+type Result<T> = std::result::Result<T, Error>;
 
-const HEIGHT_MASK: u8 = 0xF;
-const VISIBLE_BIT: u8 = 1 << 4;
+struct Tree(u32);
+
+/// Heights are stored off by one, so that 0 is below every tree.
+const HEIGHT_MASK: u32 = 0x1_FFFF;
+const VISIBLE_BIT: u32 = 1 << 17;
 
 impl Tree {
-    fn height(&self) -> u8 {
+    fn height(&self) -> u32 {
         self.0 & HEIGHT_MASK
     }
 
@@ -17,9 +21,9 @@ impl Tree {
     }
 }
 
-impl From<u8> for Tree {
-    fn from(c: u8) -> Self {
-        Tree(c - b'0' + 1)
+impl From<u16> for Tree {
+    fn from(height: u16) -> Self {
+        Tree(height as u32 + 1)
     }
 }
 
@@ -29,6 +33,16 @@ struct Grid<T> {
     cells: Vec<T>,
 }
 
+impl<T: Clone> Grid<T> {
+    fn new(height: usize, width: usize, value: T) -> Self {
+        Self {
+            height,
+            width,
+            cells: vec![value; height * width],
+        }
+    }
+}
+
 impl<T> std::ops::Index<(usize, usize)> for Grid<T> {
     type Output = T;
 
@@ -43,25 +57,46 @@ impl<T> std::ops::IndexMut<(usize, usize)> for Grid<T> {
     }
 }
 
-fn read_trees(input: &str) -> Grid<Tree> {
+#[derive(Clone, Copy)]
+enum HeightFormat {
+    /// Rows of digits: "30373".
+    Digits,
+    /// Rows of whitespace-separated heights up to 65535: "3 0 3 7 3".
+    Numeric,
+}
+
+/// Reads a non-empty forest, whose rows must all have the same length.
+fn read_trees(input: &str, format: HeightFormat) -> Result<Grid<Tree>> {
     let mut grid = Grid {
         height: 0,
         width: 0,
         cells: vec![],
     };
-    for (i, c) in input.bytes().enumerate() {
-        if c == b'\n' {
-            if grid.width == 0 {
-                grid.width = i;
-            } else {
-                assert_eq!((i + 1) % (grid.width + 1), 0);
+    for line in input.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let row_start = grid.cells.len();
+        if let HeightFormat::Numeric = format {
+            for height in line.split_whitespace() {
+                grid.cells
+                    .push(height.parse::<u16>().map_err(|_| "Invalid height")?.into());
             }
         } else {
-            grid.cells.push(c.into());
+            for c in line.chars() {
+                grid.cells
+                    .push((c.to_digit(10).ok_or("Invalid digit")? as u16).into());
+            }
         }
+        let row_width = grid.cells.len() - row_start;
+        if grid.height == 0 {
+            grid.width = row_width;
+        } else if row_width != grid.width {
+            return Err("Rows have different lengths");
+        }
+        grid.height += 1;
+    }
+    if grid.width == 0 {
+        return Err("Empty forest");
     }
-    grid.height = grid.cells.len() / grid.width;
-    grid
+    Ok(grid)
 }
 
 fn check_line<
@@ -87,12 +122,50 @@ fn check_line<
 }
 
 fn check_grid(grid: &mut Grid<Tree>) {
-    check_line(grid, 0..grid.width, 0..grid.height, |i, j| (i, j));
-    check_line(grid, 0..grid.width, (0..grid.height).rev(), |i, j| (i, j));
-    check_line(grid, 0..grid.height, 0..grid.width, |i, j| (j, i));
-    check_line(grid, 0..grid.height, (0..grid.width).rev(), |i, j| (j, i));
+    check_line(grid, 0..grid.height, 0..grid.width, |i, j| (i, j));
+    check_line(grid, 0..grid.height, (0..grid.width).rev(), |i, j| (i, j));
+    check_line(grid, 0..grid.width, 0..grid.height, |i, j| (j, i));
+    check_line(grid, 0..grid.width, (0..grid.height).rev(), |i, j| (j, i));
 }
 
+/// Multiplies each score along the line by the viewing distance looking back towards the start
+/// of the line. The stack holds the trees that can still block the view, with decreasing heights.
+fn scenic_score_line<I: Iterator<Item = usize>, F: Fn(usize) -> (usize, usize)>(
+    grid: &Grid<Tree>,
+    scores: &mut Grid<usize>,
+    range: I,
+    indexer: F,
+) {
+    let mut stack: Vec<(usize, u32)> = Vec::new();
+    for (position, k) in range.enumerate() {
+        let height = grid[indexer(k)].height();
+        while stack.last().is_some_and(|&(_, h)| h < height) {
+            stack.pop();
+        }
+        let distance = match stack.last() {
+            Some(&(blocker, _)) => position - blocker,
+            None => position,
+        };
+        scores[indexer(k)] *= distance;
+        stack.push((position, height));
+    }
+}
+
+/// Scenic score of every tree, in linear time.
+fn scenic_scores(grid: &Grid<Tree>) -> Grid<usize> {
+    let mut scores = Grid::new(grid.height, grid.width, 1);
+    for i in 0..grid.height {
+        scenic_score_line(grid, &mut scores, 0..grid.width, |j| (i, j));
+        scenic_score_line(grid, &mut scores, (0..grid.width).rev(), |j| (i, j));
+    }
+    for j in 0..grid.width {
+        scenic_score_line(grid, &mut scores, 0..grid.height, |i| (i, j));
+        scenic_score_line(grid, &mut scores, (0..grid.height).rev(), |i| (i, j));
+    }
+    scores
+}
+
+#[cfg(test)]
 fn check_treehouse_view_line<
     I: Iterator<Item = usize> + Clone + ExactSizeIterator,
     F: Fn(usize) -> (usize, usize),
@@ -100,7 +173,7 @@ fn check_treehouse_view_line<
     grid: &Grid<Tree>,
     range: I,
     indexer: F,
-    tree_height: u8,
+    tree_height: u32,
 ) -> usize {
     let range_size = range.len();
     for (i, k) in range.enumerate() {
@@ -111,12 +184,123 @@ fn check_treehouse_view_line<
     range_size
 }
 
+/// Reference implementation of the scenic score, scanning outward from a single tree.
+#[cfg(test)]
 fn check_treehouse(grid: &Grid<Tree>, i: usize, j: usize) -> usize {
     let tree_height = grid[(i, j)].height();
-    check_treehouse_view_line(&grid, (0..i).rev(), |k| (k, j), tree_height)
-        * check_treehouse_view_line(&grid, (0..j).rev(), |k| (i, k), tree_height)
-        * check_treehouse_view_line(&grid, (i + 1)..grid.height, |k| (k, j), tree_height)
-        * check_treehouse_view_line(&grid, (j + 1)..grid.width, |k| (i, k), tree_height)
+    check_treehouse_view_line(grid, (0..i).rev(), |k| (k, j), tree_height)
+        * check_treehouse_view_line(grid, (0..j).rev(), |k| (i, k), tree_height)
+        * check_treehouse_view_line(grid, (i + 1)..grid.height, |k| (k, j), tree_height)
+        * check_treehouse_view_line(grid, (j + 1)..grid.width, |k| (i, k), tree_height)
+}
+
+fn write_pgm<W: std::io::Write>(
+    out: &mut W,
+    height: usize,
+    width: usize,
+    pixels: impl Iterator<Item = u8>,
+) -> std::io::Result<()> {
+    writeln!(out, "P5\n{} {}\n255", width, height)?;
+    out.write_all(&pixels.collect::<Vec<_>>())
+}
+
+fn write_ppm<W: std::io::Write>(
+    out: &mut W,
+    height: usize,
+    width: usize,
+    pixels: impl Iterator<Item = [u8; 3]>,
+) -> std::io::Result<()> {
+    writeln!(out, "P6\n{} {}\n255", width, height)?;
+    out.write_all(&pixels.flatten().collect::<Vec<_>>())
+}
+
+fn write_csv<W: std::io::Write, T, D: std::fmt::Display>(
+    out: &mut W,
+    grid: &Grid<T>,
+    value: impl Fn(&T) -> D,
+) -> std::io::Result<()> {
+    for row in grid.cells.chunks(grid.width) {
+        let line = row
+            .iter()
+            .map(|c| value(c).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// "Hot" color ramp: black, red, yellow, white as `t` goes from 0 to 1.
+fn heat_color(t: f64) -> [u8; 3] {
+    let channel = |offset: f64| ((3.0 * t - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
+
+/// Visible trees in green and hidden ones in brown, brighter for taller trees.
+fn visibility_color(tree: &Tree, max_height: u32) -> [u8; 3] {
+    let shade = 0.4 + 0.6 * tree.height() as f64 / max_height.max(1) as f64;
+    let [r, g, b] = if tree.is_visible() {
+        [40.0, 220.0, 60.0]
+    } else {
+        [110.0, 70.0, 40.0]
+    };
+    [(r * shade) as u8, (g * shade) as u8, (b * shade) as u8]
+}
+
+fn export<W: std::io::Write>(
+    out: &mut W,
+    grid: &Grid<Tree>,
+    layer: &str,
+    format: &str,
+) -> std::io::Result<()> {
+    let (height, width) = (grid.height, grid.width);
+    match layer {
+        "visibility" => match format {
+            "pgm" => write_pgm(
+                out,
+                height,
+                width,
+                grid.cells
+                    .iter()
+                    .map(|t| if t.is_visible() { 255 } else { 0 }),
+            ),
+            "ppm" => {
+                let max_height = grid.cells.iter().map(Tree::height).max().unwrap_or(0);
+                write_ppm(
+                    out,
+                    height,
+                    width,
+                    grid.cells.iter().map(|t| visibility_color(t, max_height)),
+                )
+            }
+            "csv" => write_csv(out, grid, |t| t.is_visible() as u8),
+            _ => panic!("Unknown format: {}", format),
+        },
+        "scenic" => {
+            let scores = scenic_scores(grid);
+            let max = scores.cells.iter().copied().max().unwrap_or(0).max(1) as f64;
+            match format {
+                "pgm" => write_pgm(
+                    out,
+                    height,
+                    width,
+                    scores
+                        .cells
+                        .iter()
+                        .map(|&s| (s as f64 / max * 255.0).round() as u8),
+                ),
+                "ppm" => write_ppm(
+                    out,
+                    height,
+                    width,
+                    scores.cells.iter().map(|&s| heat_color(s as f64 / max)),
+                ),
+                "csv" => write_csv(out, &scores, |&s| s),
+                _ => panic!("Unknown format: {}", format),
+            }
+        }
+        _ => panic!("Unknown layer: {}", layer),
+    }
 }
 
 fn main() {
@@ -126,18 +310,129 @@ fn main() {
         std::io::stdin().read_to_string(&mut contents).unwrap();
         contents
     };
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let format = match args.iter().position(|a| a == "--numeric") {
+        Some(i) => {
+            args.remove(i);
+            HeightFormat::Numeric
+        }
+        None => HeightFormat::Digits,
+    };
     let grid = {
-        let mut grid = read_trees(&contents);
+        let mut grid = read_trees(&contents, format).unwrap();
         check_grid(&mut grid);
         grid
     };
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => (),
+        ["export", layer, format] => {
+            let stdout = std::io::stdout();
+            let mut out = std::io::BufWriter::new(stdout.lock());
+            return export(&mut out, &grid, layer, format).expect("Error writing stdout");
+        }
+        _ => panic!("Usage: day08 [export <visibility | scenic> <pgm | ppm | csv>] [--numeric]"),
+    }
     println!("{}", grid.cells.iter().filter(|t| t.is_visible()).count());
-    let for_each_inner_cell = (1..(grid.height - 1))
-        .map(|w| (1..(grid.width - 1)).map(move |h| (w, h)))
-        .flatten();
-    let max_score = for_each_inner_cell
-        .map(|(i, j)| check_treehouse(&grid, i, j))
-        .max()
-        .unwrap();
-    println!("{}", max_score);
+    let scores = scenic_scores(&grid);
+    println!("{}", scores.cells.iter().max().unwrap_or(&0));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenic_scores_match_reference() {
+        // Pseudo-random forests of various shapes, with few distinct heights to get ties.
+        let mut state = 12345u32;
+        for (height, width) in [(5usize, 5usize), (1, 7), (7, 1), (3, 9), (20, 20)] {
+            let mut input = String::new();
+            for _ in 0..height {
+                for _ in 0..width {
+                    state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                    input.push((b'0' + (state >> 16) as u8 % 4) as char);
+                }
+                input.push('\n');
+            }
+            let grid = read_trees(&input, HeightFormat::Digits).unwrap();
+            let scores = scenic_scores(&grid);
+            for i in 1..height.saturating_sub(1) {
+                for j in 1..width.saturating_sub(1) {
+                    assert_eq!(scores[(i, j)], check_treehouse(&grid, i, j), "{}", input);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_visibility_of_rectangular_forests() {
+        let count_visible = |input: &str, format| {
+            let mut grid = read_trees(input, format).unwrap();
+            check_grid(&mut grid);
+            grid.cells.iter().filter(|t| t.is_visible()).count()
+        };
+        assert_eq!(
+            count_visible("30373\n25512\n65332\n33549\n35390\n", HeightFormat::Digits),
+            21
+        );
+        assert_eq!(
+            count_visible("3037\n2551\n6533\n", HeightFormat::Digits),
+            12
+        );
+        assert_eq!(count_visible("30373\n", HeightFormat::Digits), 5);
+        assert_eq!(count_visible("3\n0\n3\n", HeightFormat::Digits), 3);
+        assert_eq!(
+            count_visible("300 1000 60000\n2 999 3\n1 1 1\n", HeightFormat::Numeric),
+            9
+        );
+        assert_eq!(
+            count_visible(
+                "100 1000 100\n1000 999 1000\n100 1000 100\n",
+                HeightFormat::Numeric
+            ),
+            8
+        );
+        assert_eq!(count_visible("300\n1000\n5\n", HeightFormat::Numeric), 3);
+        assert!(read_trees("123\n12\n", HeightFormat::Digits).is_err());
+        assert_eq!(
+            read_trees("", HeightFormat::Digits).err(),
+            Some("Empty forest")
+        );
+        assert_eq!(
+            read_trees("\n\n", HeightFormat::Numeric).err(),
+            Some("Empty forest")
+        );
+    }
+
+    #[test]
+    fn test_exports() {
+        let mut grid =
+            read_trees("30373\n25512\n65332\n33549\n35390\n", HeightFormat::Digits).unwrap();
+        check_grid(&mut grid);
+        let export_to_vec = |layer, format| {
+            let mut out = Vec::new();
+            export(&mut out, &grid, layer, format).unwrap();
+            out
+        };
+        let header = b"P5\n5 5\n255\n";
+        let pgm = export_to_vec("visibility", "pgm");
+        assert_eq!(&pgm[..header.len()], header);
+        assert_eq!(pgm.len(), header.len() + 25);
+        // White for the 21 visible trees.
+        let visible = pgm[header.len()..].iter().filter(|&&p| p == 255);
+        assert_eq!(visible.count(), 21);
+        let header = b"P6\n5 5\n255\n";
+        let ppm = export_to_vec("scenic", "ppm");
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 3 * 25);
+        assert_eq!(
+            String::from_utf8(export_to_vec("scenic", "csv")).unwrap(),
+            "0,0,0,0,0\n0,1,4,1,0\n0,6,1,2,0\n0,1,8,3,0\n0,0,0,0,0\n"
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
 struct Point {
@@ -23,63 +23,201 @@ impl std::ops::Add<Direction> for Point {
     }
 }
 
-type Rope<const N: usize> = [Point; N];
-
 fn parse_line<S: AsRef<str>>(line: S) -> (Direction, u16) {
-    let bytes = line.as_ref().as_bytes();
-    let direction = match bytes[0] {
-        b'U' => Direction { x: -1, y: 0 },
-        b'D' => Direction { x: 1, y: 0 },
-        b'L' => Direction { x: 0, y: -1 },
-        b'R' => Direction { x: 0, y: 1 },
-        _ => panic!(),
+    let (direction, distance) = line.as_ref().split_once(' ').expect("Missing distance");
+    let direction = match direction {
+        "U" => Direction { x: -1, y: 0 },
+        "D" => Direction { x: 1, y: 0 },
+        "L" => Direction { x: 0, y: -1 },
+        "R" => Direction { x: 0, y: 1 },
+        "UL" => Direction { x: -1, y: -1 },
+        "UR" => Direction { x: -1, y: 1 },
+        "DL" => Direction { x: 1, y: -1 },
+        "DR" => Direction { x: 1, y: 1 },
+        _ => panic!("Unknown direction: {}", direction),
     };
     (
         direction,
-        std::str::from_utf8(&bytes[2..])
-            .unwrap()
-            .parse::<u16>()
-            .unwrap(),
+        distance.parse::<u16>().expect("Invalid distance"),
     )
 }
 
 fn pull_rope(head: Point, tail: Point) -> Point {
     if head.x.abs_diff(tail.x) > 1 || head.y.abs_diff(tail.y) > 1 {
         Point {
-            x: ((head.x * 2 + tail.x) as f32 / 3.0).round() as i32,
-            y: ((head.y * 2 + tail.y) as f32 / 3.0).round() as i32,
+            x: tail.x + (head.x - tail.x).signum(),
+            y: tail.y + (head.y - tail.y).signum(),
         }
     } else {
         tail
     }
 }
 
-fn apply_singe_move<const N: usize>(
-    mut rope: Rope<N>,
-    direction: Direction,
-    distance: u16,
-    mut positions: HashSet<Point>,
-) -> (Rope<N>, HashSet<Point>) {
-    for _ in 0..distance {
-        rope[0] = rope[0] + direction;
-        for i in 0..(N - 1) {
-            rope[i + 1] = pull_rope(rope[i], rope[i + 1]);
+/// Inclusive bounds of a set of points.
+struct BoundingBox {
+    min: Point,
+    max: Point,
+}
+
+impl BoundingBox {
+    fn of<'a>(points: impl Iterator<Item = &'a Point>) -> Self {
+        points.fold(
+            BoundingBox {
+                min: Point {
+                    x: i32::MAX,
+                    y: i32::MAX,
+                },
+                max: Point {
+                    x: i32::MIN,
+                    y: i32::MIN,
+                },
+            },
+            |b, p| BoundingBox {
+                min: Point {
+                    x: b.min.x.min(p.x),
+                    y: b.min.y.min(p.y),
+                },
+                max: Point {
+                    x: b.max.x.max(p.x),
+                    y: b.max.y.max(p.y),
+                },
+            },
+        )
+    }
+}
+
+struct Rope {
+    knots: Vec<Point>,
+    /// Number of times each knot was at each position, including the start.
+    visits: Vec<HashMap<Point, usize>>,
+    /// Number of single steps of the head so far.
+    step: usize,
+}
+
+impl Rope {
+    fn new(num_knots: usize) -> Self {
+        assert!(num_knots > 0, "A rope needs at least one knot");
+        let start = Point::default();
+        Self {
+            knots: vec![start; num_knots],
+            visits: vec![HashMap::from([(start, 1)]); num_knots],
+            step: 0,
+        }
+    }
+
+    /// Applies the move one step at a time, calling `on_step` with the step number and the
+    /// knot positions after each step.
+    fn apply_move_with(
+        &mut self,
+        direction: Direction,
+        distance: u16,
+        mut on_step: impl FnMut(usize, &[Point]),
+    ) {
+        for _ in 0..distance {
+            self.knots[0] = self.knots[0] + direction;
+            for i in 0..(self.knots.len() - 1) {
+                self.knots[i + 1] = pull_rope(self.knots[i], self.knots[i + 1]);
+            }
+            for (knot, visits) in self.knots.iter().zip(self.visits.iter_mut()) {
+                *visits.entry(*knot).or_default() += 1;
+            }
+            self.step += 1;
+            on_step(self.step, &self.knots);
         }
-        positions.insert(rope[N - 1]);
     }
-    (rope, positions)
+
+    fn apply_move(&mut self, direction: Direction, distance: u16) {
+        self.apply_move_with(direction, distance, |_, _| ())
+    }
+
+    fn tail_visits(&self) -> usize {
+        self.visits.last().unwrap().len()
+    }
+
+    /// Per-knot report: distinct cells, most visited cell and bounding box.
+    fn stats(&self) -> String {
+        let mut output = String::new();
+        for (i, visits) in self.visits.iter().enumerate() {
+            let (most_visited, count) = visits
+                .iter()
+                .max_by_key(|(p, &count)| (count, std::cmp::Reverse((p.x, p.y))))
+                .unwrap();
+            let bounds = BoundingBox::of(visits.keys());
+            output.push_str(&format!(
+                "Knot {}: {} distinct cells, most visited ({}, {}) {} times, \
+                 bounding box ({}, {})..=({}, {})\n",
+                i,
+                visits.len(),
+                most_visited.x,
+                most_visited.y,
+                count,
+                bounds.min.x,
+                bounds.min.y,
+                bounds.max.x,
+                bounds.max.y
+            ));
+        }
+        output
+    }
+
+    /// ASCII map of the cells visited by each knot, all drawn over the same area: `s` is the
+    /// start, `#` a visited cell.
+    fn render(&self) -> String {
+        let bounds = BoundingBox::of(self.visits.iter().flat_map(HashMap::keys));
+        let mut output = String::new();
+        for (i, visits) in self.visits.iter().enumerate() {
+            output.push_str(&format!("Knot {}:\n", i));
+            for x in bounds.min.x..=bounds.max.x {
+                for y in bounds.min.y..=bounds.max.y {
+                    let point = Point { x, y };
+                    output.push(if point == Point::default() {
+                        's'
+                    } else if visits.contains_key(&point) {
+                        '#'
+                    } else {
+                        '.'
+                    });
+                }
+                output.push('\n');
+            }
+        }
+        output
+    }
+}
+
+fn apply_all_moves(moves: &[(Direction, u16)], num_knots: usize) -> Rope {
+    let mut rope = Rope::new(num_knots);
+    for (direction, distance) in moves {
+        rope.apply_move(*direction, *distance);
+    }
+    rope
+}
+
+fn write_step<W: std::io::Write>(out: &mut W, step: usize, knots: &[Point]) -> std::io::Result<()> {
+    for (i, knot) in knots.iter().enumerate() {
+        writeln!(out, "{},{},{},{}", step, i, knot.x, knot.y)?;
+    }
+    Ok(())
 }
 
-fn apply_all_moves<const N: usize>(moves: &[(Direction, u16)]) -> usize {
-    let start = Point::default();
-    let (_, mut positions) = moves.iter().fold(
-        ([start; N], HashSet::<Point>::new()),
-        |(rope, positions), (direction, distance)| {
-            apply_singe_move(rope, *direction, *distance, positions)
-        },
-    );
-    positions.insert(start);
-    positions.len()
+/// Writes the position of every knot after every step as CSV, starting with step 0.
+fn write_trajectory<W: std::io::Write>(
+    out: &mut W,
+    moves: &[(Direction, u16)],
+    num_knots: usize,
+) -> std::io::Result<()> {
+    let mut rope = Rope::new(num_knots);
+    writeln!(out, "step,knot,x,y")?;
+    write_step(out, 0, &rope.knots)?;
+    let mut result = Ok(());
+    for (direction, distance) in moves {
+        rope.apply_move_with(*direction, *distance, |step, knots| {
+            if result.is_ok() {
+                result = write_step(out, step, knots);
+            }
+        });
+    }
+    result
 }
 
 fn main() {
@@ -88,6 +226,115 @@ fn main() {
         .map(Result::unwrap)
         .map(parse_line)
         .collect::<Vec<_>>();
-    println!("{}", apply_all_moves::<2>(&moves));
-    println!("{}", apply_all_moves::<10>(&moves));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => {
+            println!("{}", apply_all_moves(&moves, 2).tail_visits());
+            println!("{}", apply_all_moves(&moves, 10).tail_visits());
+        }
+        ["render", num_knots] => {
+            let num_knots = num_knots.parse().expect("Invalid knot count");
+            print!("{}", apply_all_moves(&moves, num_knots).render());
+        }
+        ["stats", num_knots] => {
+            let num_knots = num_knots.parse().expect("Invalid knot count");
+            print!("{}", apply_all_moves(&moves, num_knots).stats());
+        }
+        ["trajectory", num_knots] => {
+            let num_knots = num_knots.parse().expect("Invalid knot count");
+            let stdout = std::io::stdout();
+            let mut out = std::io::BufWriter::new(stdout.lock());
+            write_trajectory(&mut out, &moves, num_knots).expect("Error writing stdout");
+        }
+        knot_counts => {
+            for num_knots in knot_counts {
+                let num_knots = num_knots.parse().expect("Invalid knot count");
+                println!("{}", apply_all_moves(&moves, num_knots).tail_visits());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_moves(input: &str) -> Vec<(Direction, u16)> {
+        input.lines().map(parse_line).collect()
+    }
+
+    #[test]
+    fn test_examples() {
+        let moves = parse_moves("R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2");
+        assert_eq!(apply_all_moves(&moves, 2).tail_visits(), 13);
+        assert_eq!(apply_all_moves(&moves, 10).tail_visits(), 1);
+        let moves = parse_moves("R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20");
+        assert_eq!(apply_all_moves(&moves, 10).tail_visits(), 36);
+    }
+
+    #[test]
+    fn test_pull_rope() {
+        let pull = |head: (i32, i32), tail: (i32, i32)| {
+            let p = pull_rope(
+                Point {
+                    x: head.0,
+                    y: head.1,
+                },
+                Point {
+                    x: tail.0,
+                    y: tail.1,
+                },
+            );
+            (p.x, p.y)
+        };
+        // Touching, diagonally or not: the tail doesn't move.
+        assert_eq!(pull((1, 1), (0, 0)), (0, 0));
+        assert_eq!(pull((0, 1), (0, 0)), (0, 0));
+        assert_eq!(pull((2, 0), (0, 0)), (1, 0));
+        assert_eq!(pull((0, -2), (0, 0)), (0, -1));
+        assert_eq!(pull((2, 1), (0, 0)), (1, 1));
+        assert_eq!(pull((-1, 2), (0, 0)), (-1, 1));
+        // Only possible behind a knot that moved diagonally.
+        assert_eq!(pull((2, 2), (0, 0)), (1, 1));
+        assert_eq!(pull((-2, 2), (0, 0)), (-1, 1));
+    }
+
+    #[test]
+    fn test_diagonal_moves() {
+        let moves = parse_moves("UR 3\nDL 1\nDR 2\nUL 1");
+        let rope = apply_all_moves(&moves, 2);
+        assert_eq!(rope.knots, [Point { x: -1, y: 3 }, Point { x: -1, y: 3 }]);
+        // The tail follows the head along both diagonals, and stays put while the head is close.
+        let tail = rope.visits[1].keys().map(|p| (p.x, p.y));
+        let mut tail = tail.collect::<Vec<_>>();
+        tail.sort();
+        assert_eq!(tail, [(-2, 2), (-1, 1), (-1, 3), (0, 0)]);
+    }
+
+    #[test]
+    fn test_trajectory_and_stats() {
+        let mut csv = Vec::new();
+        write_trajectory(&mut csv, &parse_moves("R 2"), 2).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "step,knot,x,y\n0,0,0,0\n0,1,0,0\n1,0,0,1\n1,1,0,0\n2,0,0,2\n2,1,0,1\n"
+        );
+
+        let rope = apply_all_moves(&parse_moves("R 4\nU 4"), 2);
+        let bounds = BoundingBox::of(rope.visits[1].keys());
+        assert_eq!(bounds.min, Point { x: -3, y: 0 });
+        assert_eq!(bounds.max, Point { x: 0, y: 4 });
+        assert_eq!(
+            rope.stats().lines().nth(1),
+            Some(
+                "Knot 1: 7 distinct cells, most visited (0, 0) 2 times, \
+                 bounding box (-3, 0)..=(0, 4)"
+            )
+        );
+    }
 }
@@ -0,0 +1,148 @@
+//! Cycle-by-cycle trace of the CPU, with breakpoints.
+//!
+//! When a breakpoint is hit, commands are read one per line: `s` (or an empty line) runs one
+//! more cycle, `c` continues to the next breakpoint, `p` prints the registers, `screen` prints
+//! the current frame and `q` quits.
+
+use std::io::{BufRead, Write};
+
+use crate::{Crt, CrtGeometry, Register, RegisterStates, RegisterValue, Step, REGISTER_NAMES};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// `cycle=20`: stops after the given cycle.
+    Cycle(usize),
+    /// `x=5`: stops after the cycle where the register changes to the given value.
+    Register(Register, RegisterValue),
+}
+
+impl TryFrom<&str> for Breakpoint {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (name, target) = value
+            .split_once('=')
+            .ok_or("Expected cycle=<N> or <register>=<N>")?;
+        if name == "cycle" {
+            Ok(Breakpoint::Cycle(
+                target.parse().map_err(|_| "Invalid cycle")?,
+            ))
+        } else {
+            Ok(Breakpoint::Register(
+                name.try_into()?,
+                target.parse().map_err(|_| "Invalid int")?,
+            ))
+        }
+    }
+}
+
+/// Same syntax as accepted by `TryFrom`.
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle={}", cycle),
+            Breakpoint::Register(r, v) => write!(f, "{}={}", REGISTER_NAMES[r.0], v),
+        }
+    }
+}
+
+impl Breakpoint {
+    fn is_hit(&self, step: &Step) -> bool {
+        match *self {
+            Breakpoint::Cycle(cycle) => step.cycle == cycle,
+            Breakpoint::Register(r, v) => step.during[r] != v && step.after[r] == v,
+        }
+    }
+}
+
+pub struct Debugger {
+    cpu: RegisterStates,
+    crt: Crt,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub fn new(cpu: RegisterStates, geometry: CrtGeometry, breakpoints: Vec<Breakpoint>) -> Self {
+        Self {
+            cpu,
+            crt: Crt::new(geometry),
+            breakpoints,
+        }
+    }
+
+    fn trace_line(&self, step: &Step, lit: bool) -> String {
+        let CrtGeometry {
+            num_rows, num_cols, ..
+        } = self.crt.geometry;
+        let position = (step.cycle - 1) % (num_rows * num_cols);
+        format!(
+            "cycle {:>4} | {:<10} ({}/{}) | x={} -> x={} | pixel ({}, {}) {}",
+            step.cycle,
+            step.instruction.to_string(),
+            step.instruction_cycle,
+            step.instruction_cycles,
+            step.during[Register::X],
+            step.after[Register::X],
+            position / num_cols,
+            position % num_cols,
+            if lit { '#' } else { '.' }
+        )
+    }
+
+    /// Runs the program to completion, writing the trace to `out` and reading debugger commands
+    /// from `commands` when stopped. Reaching the end of `commands` continues to the end.
+    pub fn run<R: BufRead, W: Write>(&mut self, mut commands: R, mut out: W) {
+        let mut stopped = false;
+        while let Some(step) = self.cpu.step() {
+            let lit = self
+                .crt
+                .maybe_set_pixel(step.cycle - 1, step.during[Register::X]);
+            writeln!(out, "{}", self.trace_line(&step, lit)).expect("Error writing output");
+            if let Some(breakpoint) = self.breakpoints.iter().find(|b| b.is_hit(&step)) {
+                writeln!(out, "Breakpoint {} hit", breakpoint).expect("Error writing output");
+                stopped = true;
+            }
+            while stopped {
+                write!(out, "(debug) ").expect("Error writing output");
+                out.flush().expect("Error writing output");
+                let mut command = String::new();
+                if commands
+                    .read_line(&mut command)
+                    .expect("Error reading commands")
+                    == 0
+                {
+                    stopped = false;
+                    writeln!(out).expect("Error writing output");
+                    break;
+                }
+                match command.trim() {
+                    "" | "s" | "step" => break,
+                    "c" | "continue" => stopped = false,
+                    "p" | "print" => writeln!(out, "{}", step.after).expect("Error writing output"),
+                    "screen" => {
+                        if let Some(frame) = self.crt.frames.last() {
+                            writeln!(out, "{}", frame).expect("Error writing output");
+                        }
+                    }
+                    "q" | "quit" => return,
+                    command => {
+                        writeln!(out, "Unknown command: {}", command).expect("Error writing output")
+                    }
+                }
+            }
+        }
+        match self.cpu.error {
+            None => writeln!(
+                out,
+                "Halted after {} cycles: {}",
+                self.cpu.cycle, self.cpu.registers
+            ),
+            Some(error) => writeln!(
+                out,
+                "{} after {} cycles: {}",
+                error, self.cpu.cycle, self.cpu.registers
+            ),
+        }
+        .expect("Error writing output");
+    }
+}
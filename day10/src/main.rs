@@ -1,8 +1,95 @@
 #![feature(iter_intersperse)]
+mod debugger;
+mod ocr;
+
+use crate::debugger::{Breakpoint, Debugger};
+
 type RegisterValue = i32;
+
+const NUM_REGISTERS: usize = 4;
+const REGISTER_NAMES: [char; NUM_REGISTERS] = ['x', 'y', 'z', 'w'];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Register(usize);
+
+impl Register {
+    const X: Register = Register(0);
+}
+
+impl TryFrom<&str> for Register {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => REGISTER_NAMES
+                .iter()
+                .position(|&n| n == c)
+                .map(Register)
+                .ok_or("Unknown register"),
+            _ => Err("Unknown register"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RegisterFile([RegisterValue; NUM_REGISTERS]);
+
+impl Default for RegisterFile {
+    fn default() -> Self {
+        // X starts at 1, the other registers at 0.
+        let mut registers = [0; NUM_REGISTERS];
+        registers[Register::X.0] = 1;
+        Self(registers)
+    }
+}
+
+impl std::ops::Index<Register> for RegisterFile {
+    type Output = RegisterValue;
+
+    fn index(&self, index: Register) -> &Self::Output {
+        &self.0[index.0]
+    }
+}
+
+impl std::ops::IndexMut<Register> for RegisterFile {
+    fn index_mut(&mut self, index: Register) -> &mut Self::Output {
+        &mut self.0[index.0]
+    }
+}
+
+impl std::fmt::Display for RegisterFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (name, value)) in REGISTER_NAMES.iter().zip(self.0.iter()).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Instruction {
     Noop,
-    Addx(RegisterValue),
+    /// `addx 3`, `addy -2`, ...
+    Add(Register, RegisterValue),
+    /// `mulx 3`, `muly -2`, ...
+    Mul(Register, RegisterValue),
+    /// `jmp -3`: relative jump in the program.
+    Jmp(isize),
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Add(r, v) => write!(f, "add{} {}", REGISTER_NAMES[r.0], v),
+            Instruction::Mul(r, v) => write!(f, "mul{} {}", REGISTER_NAMES[r.0], v),
+            Instruction::Jmp(offset) => write!(f, "jmp {}", offset),
+        }
+    }
 }
 
 impl TryFrom<&str> for Instruction {
@@ -10,10 +97,22 @@ impl TryFrom<&str> for Instruction {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         if value == "noop" {
-            Ok(Instruction::Noop)
-        } else if let Some(int) = value.strip_prefix("addx ") {
-            Ok(Instruction::Addx(
-                int.parse::<RegisterValue>().map_err(|_| "Invalid int")?,
+            return Ok(Instruction::Noop);
+        }
+        let (op, arg) = value.split_once(' ').ok_or("Unknown instruction")?;
+        if op == "jmp" {
+            Ok(Instruction::Jmp(
+                arg.parse::<isize>().map_err(|_| "Invalid offset")?,
+            ))
+        } else if let Some(register) = op.strip_prefix("add") {
+            Ok(Instruction::Add(
+                register.try_into()?,
+                arg.parse::<RegisterValue>().map_err(|_| "Invalid int")?,
+            ))
+        } else if let Some(register) = op.strip_prefix("mul") {
+            Ok(Instruction::Mul(
+                register.try_into()?,
+                arg.parse::<RegisterValue>().map_err(|_| "Invalid int")?,
             ))
         } else {
             Err("Unknown instruction")
@@ -21,93 +120,410 @@ impl TryFrom<&str> for Instruction {
     }
 }
 
-enum CpuState {
-    Free,
-    Computing(RegisterValue),
+/// Number of cycles each kind of instruction takes to complete.
+#[derive(Clone, Copy)]
+struct CycleCounts {
+    noop: usize,
+    add: usize,
+    mul: usize,
+    jmp: usize,
+}
+
+impl Default for CycleCounts {
+    fn default() -> Self {
+        Self {
+            noop: 1,
+            add: 2,
+            mul: 2,
+            jmp: 1,
+        }
+    }
+}
+
+impl CycleCounts {
+    fn of(&self, instruction: Instruction) -> usize {
+        match instruction {
+            Instruction::Noop => self.noop,
+            Instruction::Add(..) => self.add,
+            Instruction::Mul(..) => self.mul,
+            Instruction::Jmp(_) => self.jmp,
+        }
+    }
+
+    /// Parses a `add=3`-style setting.
+    fn set(&mut self, setting: &str) -> Result<(), &'static str> {
+        let (name, count) = setting
+            .split_once('=')
+            .ok_or("Expected <instruction>=<cycles>")?;
+        let count = count.parse::<usize>().map_err(|_| "Invalid cycle count")?;
+        if count == 0 {
+            return Err("Instructions take at least one cycle");
+        }
+        match name {
+            "noop" => self.noop = count,
+            "add" => self.add = count,
+            "mul" => self.mul = count,
+            "jmp" => self.jmp = count,
+            _ => return Err("Unknown instruction"),
+        }
+        Ok(())
+    }
 }
 
-struct RegisterStates<I: Iterator<Item = Instruction>> {
-    instructions: I,
-    state: CpuState,
-    register: RegisterValue,
+/// Default limit on the number of cycles, so that endless loops such as `jmp 0` halt.
+const DEFAULT_MAX_CYCLES: usize = 1_000_000;
+
+/// Runs the program, yielding the cycle number (counting from 1) and the register values during
+/// each cycle. The effects of an instruction are visible after its last cycle.
+struct RegisterStates {
+    program: Vec<Instruction>,
+    cycle_counts: CycleCounts,
+    program_counter: usize,
+    /// Cycles left before the instruction at `program_counter` completes.
+    remaining_cycles: usize,
+    cycle: usize,
+    registers: RegisterFile,
+    max_cycles: usize,
+    /// Why the program stopped before its end, if it did.
+    error: Option<&'static str>,
 }
 
-impl<I: Iterator<Item = Instruction>> Iterator for RegisterStates<I> {
-    type Item = RegisterValue;
+impl RegisterStates {
+    fn new(program: Vec<Instruction>, cycle_counts: CycleCounts) -> Self {
+        Self {
+            program,
+            cycle_counts,
+            program_counter: 0,
+            remaining_cycles: 0,
+            cycle: 0,
+            registers: RegisterFile::default(),
+            max_cycles: DEFAULT_MAX_CYCLES,
+            error: None,
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let CpuState::Computing(mut v) = std::mem::replace(&mut self.state, CpuState::Free) {
-            std::mem::swap(&mut self.register, &mut v);
-            Some(v)
-        } else {
-            match self.instructions.next() {
-                None => None,
-                Some(Instruction::Noop) => Some(self.register),
-                Some(Instruction::Addx(v)) => {
-                    self.state = CpuState::Computing(self.register + v);
-                    Some(self.register)
-                }
+    fn with_max_cycles(mut self, max_cycles: usize) -> Self {
+        self.max_cycles = max_cycles;
+        self
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<(), &'static str> {
+        let mut next = self.program_counter as isize + 1;
+        match instruction {
+            Instruction::Noop => (),
+            Instruction::Add(r, v) => {
+                self.registers[r] = self.registers[r]
+                    .checked_add(v)
+                    .ok_or("Register overflow")?
+            }
+            Instruction::Mul(r, v) => {
+                self.registers[r] = self.registers[r]
+                    .checked_mul(v)
+                    .ok_or("Register overflow")?
+            }
+            Instruction::Jmp(offset) => next = self.program_counter as isize + offset,
+        }
+        // Jumping before the start of the program halts it, like running past the end.
+        self.program_counter = usize::try_from(next).unwrap_or(usize::MAX);
+        Ok(())
+    }
+
+    /// Panics if the program didn't run to its end.
+    fn check_halted(&self) {
+        if let Some(error) = self.error {
+            panic!("{} at cycle {}", error, self.cycle);
+        }
+    }
+}
+
+/// What happened during a single cycle.
+struct Step {
+    cycle: usize,
+    instruction: Instruction,
+    /// Which cycle of the instruction this is (counting from 1), out of `instruction_cycles`.
+    instruction_cycle: usize,
+    instruction_cycles: usize,
+    /// Register values during the cycle.
+    during: RegisterFile,
+    /// Register values after the cycle.
+    after: RegisterFile,
+}
+
+impl RegisterStates {
+    fn step(&mut self) -> Option<Step> {
+        let instruction = *self.program.get(self.program_counter)?;
+        if self.error.is_some() {
+            return None;
+        }
+        if self.cycle == self.max_cycles {
+            self.error = Some("Cycle limit reached");
+            return None;
+        }
+        let instruction_cycles = self.cycle_counts.of(instruction);
+        if self.remaining_cycles == 0 {
+            self.remaining_cycles = instruction_cycles;
+        }
+        self.cycle += 1;
+        let instruction_cycle = instruction_cycles - self.remaining_cycles + 1;
+        let during = self.registers;
+        self.remaining_cycles -= 1;
+        if self.remaining_cycles == 0 {
+            if let Err(error) = self.execute(instruction) {
+                self.error = Some(error);
+                return None;
             }
         }
+        Some(Step {
+            cycle: self.cycle,
+            instruction,
+            instruction_cycle,
+            instruction_cycles,
+            during,
+            after: self.registers,
+        })
+    }
+}
+
+impl Iterator for RegisterStates {
+    type Item = (usize, RegisterFile);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step().map(|step| (step.cycle, step.during))
     }
 }
 
-impl<I: Iterator<Item = Instruction>> From<I> for RegisterStates<I> {
+impl<I: Iterator<Item = Instruction>> From<I> for RegisterStates {
     fn from(it: I) -> Self {
-        Self {
-            instructions: it,
-            state: CpuState::Free,
-            register: 1,
-        }
+        Self::new(it.collect(), CycleCounts::default())
     }
 }
 
 const NUM_ROW: usize = 6;
 const NUM_COL: usize = 40;
 
-struct Screen([bool; NUM_ROW * NUM_COL]);
+#[derive(Clone, Copy)]
+struct CrtGeometry {
+    num_rows: usize,
+    num_cols: usize,
+    /// Number of pixels covered by the sprite, centered on X (extending to the right when even).
+    sprite_width: usize,
+}
+
+impl Default for CrtGeometry {
+    fn default() -> Self {
+        Self {
+            num_rows: NUM_ROW,
+            num_cols: NUM_COL,
+            sprite_width: 3,
+        }
+    }
+}
+
+/// A single frame of the CRT.
+struct Screen {
+    num_cols: usize,
+    pixels: Vec<bool>,
+}
 
 impl Screen {
-    fn maybe_set_pixel(&mut self, cycle: usize, register: RegisterValue) {
-        self.0[cycle] = ((cycle % NUM_COL) as i32).abs_diff(register) <= 1
+    fn new(num_rows: usize, num_cols: usize) -> Self {
+        Self {
+            num_cols,
+            pixels: vec![false; num_rows * num_cols],
+        }
+    }
+
+    fn read_letters(&self) -> Result<String, ocr::OcrError> {
+        ocr::read_letters(&self.pixels.chunks_exact(self.num_cols).collect::<Vec<_>>())
     }
 }
 
-impl ToString for Screen {
-    fn to_string(&self) -> String {
-        self.0
-            .chunks_exact(NUM_COL)
+impl std::fmt::Display for Screen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rendered = self
+            .pixels
+            .chunks_exact(self.num_cols)
             .map(|c| {
-                c.into_iter()
+                c.iter()
                     .map(|b| if *b { '#' } else { ' ' })
                     .collect::<String>()
             })
             .intersperse("\n".to_owned())
-            .collect::<String>()
+            .collect::<String>();
+        write!(f, "{}", rendered)
+    }
+}
+
+/// The CRT draws one pixel per cycle, starting a new frame once the screen is full.
+struct Crt {
+    geometry: CrtGeometry,
+    frames: Vec<Screen>,
+}
+
+impl Crt {
+    fn new(geometry: CrtGeometry) -> Self {
+        assert!(
+            geometry.num_rows > 0 && geometry.num_cols > 0,
+            "The screen can't be empty"
+        );
+        assert!(
+            RegisterValue::try_from(geometry.sprite_width).is_ok(),
+            "The sprite is too wide"
+        );
+        Self {
+            geometry,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Draws the pixel for `cycle`, counting from 0, and returns whether it is lit.
+    fn maybe_set_pixel(&mut self, cycle: usize, register: RegisterValue) -> bool {
+        let CrtGeometry {
+            num_rows,
+            num_cols,
+            sprite_width,
+        } = self.geometry;
+        let frame = cycle / (num_rows * num_cols);
+        while self.frames.len() <= frame {
+            self.frames.push(Screen::new(num_rows, num_cols));
+        }
+        let position = cycle % (num_rows * num_cols);
+        // In i64, as X can be anywhere in the register range. The width fits, see `new`.
+        let offset = (position % num_cols) as i64 - i64::from(register);
+        let before = (sprite_width as i64 - 1) / 2;
+        let lit = (-before..sprite_width as i64 - before).contains(&offset);
+        self.frames[frame].pixels[position] = lit;
+        lit
+    }
+}
+
+/// Cycles at which the signal strength is sampled: `first`, then every `period` cycles.
+#[derive(Clone, Copy)]
+struct SignalSampling {
+    first: usize,
+    period: usize,
+}
+
+impl Default for SignalSampling {
+    fn default() -> Self {
+        Self {
+            first: 20,
+            period: NUM_COL,
+        }
     }
 }
 
+impl SignalSampling {
+    fn is_sampled(&self, cycle: usize) -> bool {
+        cycle >= self.first && (cycle - self.first).is_multiple_of(self.period)
+    }
+}
+
+const USAGE: &str = "Usage: day10 [run | trace | debug <program>] [--break <cycle=N | x=N>]... \
+                     [--cycles <instruction>=<cycles>]... [--rows <rows>] \
+                     [--cols <cols>] [--sprite <width>] [--sample <first>,<period>] \
+                     [--max-cycles <cycles>]";
+
 fn main() {
-    let mut screen = Screen([false; NUM_ROW * NUM_COL]);
+    let mut cycle_counts = CycleCounts::default();
+    let mut geometry = CrtGeometry::default();
+    let mut sampling = SignalSampling::default();
+    let mut run = false;
+    let mut trace = false;
+    let mut debug_program = None;
+    let mut breakpoints = Vec::new();
+    let mut max_cycles = DEFAULT_MAX_CYCLES;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect(USAGE);
+        let mut number = || value().parse::<usize>().expect("Invalid number");
+        match arg.as_str() {
+            "run" => run = true,
+            "trace" => trace = true,
+            "debug" => debug_program = Some(value()),
+            "--break" => breakpoints.push(Breakpoint::try_from(value().as_str()).unwrap()),
+            "--cycles" => cycle_counts.set(&value()).unwrap(),
+            "--rows" => geometry.num_rows = number(),
+            "--cols" => geometry.num_cols = number(),
+            "--sprite" => geometry.sprite_width = number(),
+            "--max-cycles" => max_cycles = number(),
+            "--sample" => {
+                let value = value();
+                let (first, period) = value.split_once(',').expect(USAGE);
+                sampling = SignalSampling {
+                    first: first.parse().expect("Invalid number"),
+                    period: period.parse().expect("Invalid number"),
+                };
+                assert!(sampling.period > 0, "The sampling period must be positive");
+            }
+            _ => panic!("{}", USAGE),
+        }
+    }
+    if let Some(path) = debug_program {
+        let program = std::fs::read_to_string(path)
+            .expect("Error reading program")
+            .lines()
+            .map(Instruction::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut debugger = Debugger::new(
+            RegisterStates::new(program, cycle_counts).with_max_cycles(max_cycles),
+            geometry,
+            breakpoints,
+        );
+        return debugger.run(std::io::stdin().lock(), std::io::stdout().lock());
+    }
+    let program = std::io::stdin()
+        .lines()
+        .map(Result::unwrap)
+        .map(|s| Instruction::try_from(s.as_str()))
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+    if trace {
+        let mut debugger = Debugger::new(
+            RegisterStates::new(program, cycle_counts).with_max_cycles(max_cycles),
+            geometry,
+            breakpoints,
+        );
+        return debugger.run(std::io::empty(), std::io::stdout().lock());
+    }
+    if run {
+        let mut states = RegisterStates::new(program, cycle_counts).with_max_cycles(max_cycles);
+        let (cycles, registers) = states
+            .by_ref()
+            .last()
+            .unwrap_or((0, RegisterFile::default()));
+        states.check_halted();
+        println!("Last cycle {}: {}", cycles, registers);
+        return;
+    }
+    let mut crt = Crt::new(geometry);
+    let mut states = RegisterStates::new(program, cycle_counts).with_max_cycles(max_cycles);
     println!(
         "{}",
-        RegisterStates::<_>::from(
-            std::io::stdin()
-                .lines()
-                .map(Result::unwrap)
-                .map(|s| Instruction::try_from(s.as_str()))
-                .map(Result::unwrap),
-        )
-        .enumerate()
-        .map(|(i, r)| {
-            screen.maybe_set_pixel(i, r);
-            (i, r)
-        })
-        .map(|(i, r)| ((i + 1) as i32, r)) // count from 1
-        .filter_map(|(i, r)| if i % 40 == 20 { Some(i * r) } else { None })
-        .sum::<i32>()
+        states
+            .by_ref()
+            .map(|(cycle, registers)| {
+                crt.maybe_set_pixel(cycle - 1, registers[Register::X]);
+                (cycle, registers[Register::X])
+            })
+            .filter(|(cycle, _)| sampling.is_sampled(*cycle))
+            .map(|(cycle, r)| cycle as i128 * i128::from(r))
+            .sum::<i128>()
     );
-    println!("{}", screen.to_string());
+    states.check_halted();
+    for (i, screen) in crt.frames.iter().enumerate() {
+        if crt.frames.len() > 1 {
+            println!("Frame {}:", i);
+        }
+        println!("{}", screen);
+        if geometry.num_rows == ocr::GLYPH_HEIGHT {
+            match screen.read_letters() {
+                Ok(letters) => println!("{}", letters.trim_end()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -117,20 +533,145 @@ mod tests {
     #[test]
     fn short_test() {
         assert_eq!(
-            RegisterStates::<_>::from(
+            RegisterStates::from(
                 [
                     Instruction::Noop,
-                    Instruction::Addx(3),
-                    Instruction::Addx(-5),
+                    Instruction::Add(Register::X, 3),
+                    Instruction::Add(Register::X, -5),
                     Instruction::Noop,
                     Instruction::Noop,
-                    Instruction::Addx(3),
+                    Instruction::Add(Register::X, 3),
                     Instruction::Noop,
                 ]
                 .into_iter()
             )
+            .map(|(_, registers)| registers[Register::X])
             .collect::<Vec<_>>(),
             vec![1, 1, 1, 4, 4, -1, -1, -1, -1, 2]
         );
     }
+
+    #[test]
+    fn extended_instructions() {
+        let program = ["addy 3", "muly 4", "jmp 2", "addx 100", "addz -1"]
+            .into_iter()
+            .map(Instruction::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut cycle_counts = CycleCounts::default();
+        cycle_counts.set("mul=3").unwrap();
+        let states = RegisterStates::new(program, cycle_counts).collect::<Vec<_>>();
+        assert_eq!(
+            states.iter().map(|(cycle, _)| *cycle).collect::<Vec<_>>(),
+            (1..=8).collect::<Vec<_>>()
+        );
+        assert_eq!(states[2].1, RegisterFile([1, 3, 0, 0]));
+        assert_eq!(states[5].1, RegisterFile([1, 12, 0, 0]));
+        assert_eq!(states[7].1, RegisterFile([1, 12, 0, 0]));
+        assert_eq!(Instruction::try_from("addq 1"), Err("Unknown register"));
+    }
+
+    #[test]
+    fn read_screen_letters() {
+        let rows = [
+            "#..#.####.#....###..###..#...#..........",
+            "#..#.#....#....#..#.#..#.#..............",
+            "####.###..#....###..#..#.#..............",
+            "#..#.#....#....##.#.###..#..............",
+            "#..#.#....#....#..#.#.#..#..............",
+            "#..#.####.####.###..#..#.####...........",
+        ];
+        let mut screen = Screen::new(NUM_ROW, NUM_COL);
+        for (i, row) in rows.iter().enumerate() {
+            for (j, c) in row.bytes().enumerate() {
+                screen.pixels[i * NUM_COL + j] = c == b'#';
+            }
+        }
+        let error = screen.read_letters().unwrap_err();
+        assert_eq!(error.partial, "HEL?R?  ");
+        assert_eq!(
+            error.unknown.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            vec![3, 5]
+        );
+        assert_eq!(
+            error.unknown[0].1,
+            "###..\n#..#.\n###..\n##.#.\n#..#.\n###.."
+        );
+        screen.pixels[3 * NUM_COL + 16] = false;
+        screen.pixels[29] = false;
+        assert_eq!(screen.read_letters(), Ok("HELBRL  ".to_owned()));
+    }
+
+    #[test]
+    fn crt_frames() {
+        let mut crt = Crt::new(CrtGeometry {
+            num_rows: 2,
+            num_cols: 4,
+            sprite_width: 2,
+        });
+        for cycle in 0..10 {
+            crt.maybe_set_pixel(cycle, 1);
+        }
+        assert_eq!(crt.frames.len(), 2);
+        assert_eq!(crt.frames[0].to_string(), " ## \n ## ");
+        assert_eq!(crt.frames[1].to_string(), " #  \n    ");
+
+        let mut crt = Crt::new(CrtGeometry::default());
+        assert!(!crt.maybe_set_pixel(0, RegisterValue::MAX));
+        assert!(!crt.maybe_set_pixel(1, RegisterValue::MIN));
+        assert!(crt.maybe_set_pixel(2, 1));
+    }
+
+    #[test]
+    fn runaway_programs() {
+        let parse = |program: &[&str]| {
+            program
+                .iter()
+                .map(|&i| Instruction::try_from(i))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+        let mut states = RegisterStates::new(parse(&["addx 1", "jmp 0"]), CycleCounts::default())
+            .with_max_cycles(100);
+        assert_eq!(states.by_ref().count(), 100);
+        assert_eq!(states.error, Some("Cycle limit reached"));
+        assert!(states.next().is_none());
+
+        let mut states = RegisterStates::new(
+            parse(&["addx 99", "mulx 1000", "jmp -1"]),
+            CycleCounts::default(),
+        );
+        assert_eq!(states.by_ref().last().unwrap().1[Register::X], 100_000_000);
+        assert_eq!(states.error, Some("Register overflow"));
+        assert_eq!(states.cycle, 10);
+    }
+
+    #[test]
+    fn debugger_breakpoints() {
+        let program = ["noop", "addx 3", "addx -5", "noop"]
+            .into_iter()
+            .map(Instruction::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let breakpoints = vec![
+            Breakpoint::try_from("x=4").unwrap(),
+            Breakpoint::try_from("cycle=5").unwrap(),
+        ];
+        let mut debugger = Debugger::new(
+            RegisterStates::new(program, CycleCounts::default()),
+            CrtGeometry::default(),
+            breakpoints,
+        );
+        let mut out = Vec::new();
+        debugger.run("c\nq\n".as_bytes(), &mut out);
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(
+            lines[2],
+            "cycle    3 | addx 3     (2/2) | x=1 -> x=4 | pixel (0, 2) #"
+        );
+        assert_eq!(lines[3], "Breakpoint x=4 hit");
+        assert_eq!(lines[6], "Breakpoint cycle=5 hit");
+    }
 }
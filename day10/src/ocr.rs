@@ -0,0 +1,105 @@
+//! Reads the letters drawn on the CRT with the 4x6 Advent of Code font.
+
+pub const GLYPH_HEIGHT: usize = 6;
+const GLYPH_WIDTH: usize = 4;
+/// Letters are separated by a blank column.
+const GLYPH_STRIDE: usize = GLYPH_WIDTH + 1;
+
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Some glyphs on the screen are not in the font. Unknown glyphs are shown as `?` in `partial`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OcrError {
+    pub partial: String,
+    /// Index of each unknown glyph, with its rendering.
+    pub unknown: Vec<(usize, String)>,
+}
+
+impl std::fmt::Display for OcrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown glyphs in \"{}\":", self.partial)?;
+        for (index, rendering) in &self.unknown {
+            write!(f, "\nGlyph {}:\n{}", index, rendering)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders the glyph starting at `column`, including the separating column if there is one.
+fn render_glyph(rows: &[&[bool]], column: usize) -> String {
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .skip(column)
+                .take(GLYPH_STRIDE)
+                .map(|&b| if b { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn match_glyph(rows: &[&[bool]], column: usize) -> Option<char> {
+    let is_lit = |row: usize, col: usize| rows[row].get(column + col).copied().unwrap_or(false);
+    if (0..GLYPH_HEIGHT).any(|row| is_lit(row, GLYPH_WIDTH)) {
+        return None;
+    }
+    if (0..GLYPH_HEIGHT).all(|row| (0..GLYPH_WIDTH).all(|col| !is_lit(row, col))) {
+        return Some(' ');
+    }
+    GLYPHS
+        .iter()
+        .find(|(_, pattern)| {
+            pattern.iter().enumerate().all(|(row, line)| {
+                line.bytes()
+                    .enumerate()
+                    .all(|(col, c)| (c == b'#') == is_lit(row, col))
+            })
+        })
+        .map(|(letter, _)| *letter)
+}
+
+/// Reads the letters from rows of pixels. Blank glyphs are read as spaces.
+pub fn read_letters(rows: &[&[bool]]) -> Result<String, OcrError> {
+    assert_eq!(
+        rows.len(),
+        GLYPH_HEIGHT,
+        "The font is {} pixels high",
+        GLYPH_HEIGHT
+    );
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut partial = String::new();
+    let mut unknown = Vec::new();
+    for (index, column) in (0..width).step_by(GLYPH_STRIDE).enumerate() {
+        match match_glyph(rows, column) {
+            Some(letter) => partial.push(letter),
+            None => {
+                partial.push('?');
+                unknown.push((index, render_glyph(rows, column)));
+            }
+        }
+    }
+    if unknown.is_empty() {
+        Ok(partial)
+    } else {
+        Err(OcrError { partial, unknown })
+    }
+}
@@ -0,0 +1,124 @@
+//! Just enough arbitrary-precision arithmetic to follow the exact worry levels.
+
+use std::cmp::Ordering;
+
+/// Unsigned big integer, as little-endian 32-bit limbs without trailing zeros.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigUint(Vec<u32>);
+
+impl From<u64> for BigUint {
+    fn from(mut value: u64) -> Self {
+        let mut limbs = Vec::new();
+        while value > 0 {
+            limbs.push(value as u32);
+            value >>= 32;
+        }
+        Self(limbs)
+    }
+}
+
+impl BigUint {
+    fn normalized(mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self(limbs)
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let mut limbs = Vec::with_capacity(self.0.len().max(other.0.len()) + 1);
+        let mut carry = 0u64;
+        for i in 0..self.0.len().max(other.0.len()) {
+            let sum =
+                *self.0.get(i).unwrap_or(&0) as u64 + *other.0.get(i).unwrap_or(&0) as u64 + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        Self::normalized(limbs)
+    }
+
+    /// Returns `None` if the result would be negative.
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        if self.cmp(other) == Ordering::Less {
+            return None;
+        }
+        let mut limbs = Vec::with_capacity(self.0.len());
+        let mut borrow = 0i64;
+        for i in 0..self.0.len() {
+            let mut diff = self.0[i] as i64 - *other.0.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = 0;
+            if diff < 0 {
+                diff += 1 << 32;
+                borrow = 1;
+            }
+            limbs.push(diff as u32);
+        }
+        Some(Self::normalized(limbs))
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut limbs = vec![0u32; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.0.iter().enumerate() {
+                let product = a as u64 * b as u64 + limbs[i + j] as u64 + carry;
+                limbs[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            limbs[i + other.0.len()] = carry as u32;
+        }
+        Self::normalized(limbs)
+    }
+
+    /// Quotient and remainder of the division by a machine integer.
+    pub fn div_rem_small(&self, divisor: u64) -> (Self, u64) {
+        assert!(divisor > 0, "Division by zero");
+        let mut limbs = vec![0u32; self.0.len()];
+        let mut remainder = 0u128;
+        for i in (0..self.0.len()).rev() {
+            let current = (remainder << 32) | self.0[i] as u128;
+            limbs[i] = (current / divisor as u128) as u32;
+            remainder = current % divisor as u128;
+        }
+        (Self::normalized(limbs), remainder as u64)
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+impl std::fmt::Display for BigUint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const CHUNK: u64 = 1_000_000_000;
+        let mut chunks = Vec::new();
+        let mut rest = self.clone();
+        while !rest.0.is_empty() {
+            let (quotient, remainder) = rest.div_rem_small(CHUNK);
+            chunks.push(remainder);
+            rest = quotient;
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((most_significant, others)) => {
+                write!(f, "{}", most_significant)?;
+                for chunk in others.iter().rev() {
+                    write!(f, "{:09}", chunk)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Arithmetic expressions for the monkeys' operations, such as `old * 19`, `3 + old` or
+//! `(old - 2) * old`.
+
+use crate::{big::BigUint, Item};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Old,
+    Constant(Item),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+}
+
+impl Expr {
+    /// Evaluates the expression for the given old value, modulo `modulus`. Every intermediate
+    /// result is reduced, with a `u128` product, so that operations of any degree fit, and a
+    /// subtraction that would go below zero wraps around: the result stays congruent for the
+    /// divisibility tests.
+    pub fn evaluate(&self, old: Item, modulus: Item) -> Item {
+        match self {
+            Expr::Old => old % modulus,
+            Expr::Constant(v) => *v % modulus,
+            Expr::Binary(left, op, right) => {
+                let left = left.evaluate(old, modulus) as u128;
+                let right = right.evaluate(old, modulus) as u128;
+                let modulus = modulus as u128;
+                (match op {
+                    BinaryOp::Add => (left + right) % modulus,
+                    BinaryOp::Multiply => left * right % modulus,
+                    BinaryOp::Subtract => (left + modulus - right) % modulus,
+                }) as Item
+            }
+        }
+    }
+
+    /// Evaluates the expression without any modulus, failing if a subtraction goes below zero.
+    pub fn evaluate_exact(&self, old: &BigUint) -> Result<BigUint, &'static str> {
+        match self {
+            Expr::Old => Ok(old.clone()),
+            Expr::Constant(v) => Ok(BigUint::from(*v as u64)),
+            Expr::Binary(left, op, right) => {
+                let left = left.evaluate_exact(old)?;
+                let right = right.evaluate_exact(old)?;
+                match op {
+                    BinaryOp::Add => Ok(left.add(&right)),
+                    BinaryOp::Multiply => Ok(left.mul(&right)),
+                    BinaryOp::Subtract => left.checked_sub(&right).ok_or("Negative worry level"),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Old,
+    Number(Item),
+    Op(BinaryOp),
+    Open,
+    Close,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '+' | '-' | '*' => {
+                tokens.push(Token::Op(match c {
+                    '+' => BinaryOp::Add,
+                    '-' => BinaryOp::Subtract,
+                    _ => BinaryOp::Multiply,
+                }));
+                1
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                1
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                1
+            }
+            '0'..='9' => {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                tokens.push(Token::Number(
+                    rest[..len].parse().map_err(|_| "Invalid constant")?,
+                ));
+                len
+            }
+            _ if rest.starts_with("old") => {
+                tokens.push(Token::Old);
+                3
+            }
+            _ => return Err("Invalid operation"),
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, &'static str> {
+        let mut left = self.term()?;
+        while let Some(&Token::Op(op @ (BinaryOp::Add | BinaryOp::Subtract))) = self.tokens.peek() {
+            self.tokens.next();
+            left = Expr::Binary(Box::new(left), op, Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    /// term := atom ('*' atom)*
+    fn term(&mut self) -> Result<Expr, &'static str> {
+        let mut left = self.atom()?;
+        while self
+            .tokens
+            .next_if_eq(&Token::Op(BinaryOp::Multiply))
+            .is_some()
+        {
+            left = Expr::Binary(Box::new(left), BinaryOp::Multiply, Box::new(self.atom()?));
+        }
+        Ok(left)
+    }
+
+    /// atom := 'old' | number | '(' expr ')'
+    fn atom(&mut self) -> Result<Expr, &'static str> {
+        match self.tokens.next() {
+            Some(Token::Old) => Ok(Expr::Old),
+            Some(Token::Number(v)) => Ok(Expr::Constant(v)),
+            Some(Token::Open) => {
+                let inner = self.expr()?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err("Unbalanced parenthesis"),
+                }
+            }
+            _ => Err("Expected old, a constant or a parenthesis"),
+        }
+    }
+}
+
+impl TryFrom<&str> for Expr {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut parser = Parser {
+            tokens: tokenize(value)?.into_iter().peekable(),
+        };
+        let expr = parser.expr()?;
+        if parser.tokens.next().is_some() {
+            return Err("Trailing tokens in operation");
+        }
+        Ok(expr)
+    }
+}
@@ -1,23 +1,13 @@
-#![feature(iter_array_chunks, iterator_try_collect)]
+#![feature(iterator_try_collect)]
 
-use std::collections::VecDeque;
-type Item = usize;
+mod big;
+mod expr;
 
-enum Op {
-    Square,
-    Add(usize),
-    Multiply(usize),
-}
+use std::collections::{HashMap, VecDeque};
 
-impl Op {
-    fn apply(&self, item: Item, worried: bool) -> Item {
-        (match &self {
-            Op::Square => item * item,
-            Op::Add(v) => item + v,
-            Op::Multiply(v) => item * v,
-        }) / (if worried { 1 } else { 3 })
-    }
-}
+use crate::{big::BigUint, expr::Expr};
+
+type Item = usize;
 
 struct Action {
     divisible_by: usize,
@@ -27,7 +17,15 @@ struct Action {
 
 impl Action {
     fn get_target(&self, item: Item) -> usize {
-        if item % self.divisible_by == 0 {
+        if item.is_multiple_of(self.divisible_by) {
+            self.if_true
+        } else {
+            self.if_false
+        }
+    }
+
+    fn get_target_exact(&self, item: &BigUint) -> usize {
+        if item.div_rem_small(self.divisible_by as u64).1 == 0 {
             self.if_true
         } else {
             self.if_false
@@ -36,76 +34,103 @@ impl Action {
 }
 
 struct Monkey {
-    operation: Op,
+    operation: Expr,
     action: Action,
 }
 
-struct Throw {
-    item: Item,
+struct Throw<T = Item> {
+    item: T,
     to: usize,
 }
 
 impl Monkey {
-    fn throw_one_item(&self, item: Item, common_divisor: usize, worried: bool) -> Throw {
-        let item = self.operation.apply(item, worried) % common_divisor;
+    /// After the inspection, the worry level is divided by `relief`: 3 when the monkeys get
+    /// bored with the item, 1 when there is no relief. The inspection is reduced modulo
+    /// `common_divisor * relief`, which leaves the quotient unchanged modulo `common_divisor`.
+    fn throw_one_item(&self, item: Item, common_divisor: usize, relief: Item) -> Throw {
+        let item = self.operation.evaluate(item, common_divisor * relief) / relief % common_divisor;
         Throw {
             item,
             to: self.action.get_target(item),
         }
     }
-}
 
-fn parse_monkey(value: [String; 7]) -> Result<(Monkey, VecDeque<Item>), &'static str> {
-    if !value[0].starts_with("Monkey ") {
-        return Err("No monkey");
+    /// Same as `throw_one_item`, with the exact worry level instead of its remainder.
+    fn throw_one_item_exact(
+        &self,
+        item: &BigUint,
+        relief: Item,
+    ) -> Result<Throw<BigUint>, &'static str> {
+        let (item, _) = self
+            .operation
+            .evaluate_exact(item)?
+            .div_rem_small(relief as u64);
+        Ok(Throw {
+            to: self.action.get_target_exact(&item),
+            item,
+        })
     }
-    let current_items = value[1]
-        .strip_prefix("  Starting items: ")
+}
+
+/// Strips the words of `prefix` from the start of the line, whatever the whitespace around
+/// them, and returns the trimmed rest of the line.
+fn strip_words<'a>(line: &'a str, prefix: &[&str]) -> Option<&'a str> {
+    prefix.iter().try_fold(line.trim(), |rest, word| {
+        rest.strip_prefix(word).map(str::trim_start)
+    })
+}
+
+/// Parses a block of lines describing one monkey, returning its index too.
+fn parse_monkey(block: &[&str]) -> Result<(usize, Monkey, VecDeque<Item>), &'static str> {
+    let [header, items, operation, test, if_true, if_false] = block else {
+        return Err("A monkey is described by 6 lines");
+    };
+    let index = strip_words(header, &["Monkey"])
+        .and_then(|h| h.strip_suffix(':'))
+        .ok_or("No monkey")?
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| "Invalid monkey index")?;
+    let current_items = strip_words(items, &["Starting", "items:"])
         .ok_or("No items")?
-        .split(", ")
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
         .map(str::parse::<Item>)
         .try_collect()
         .map_err(|_| "Invalid item")?;
-    let operation = {
-        let op = value[2]
-            .strip_prefix("  Operation: new = old ")
-            .ok_or("No op")?;
-        if op == "* old" {
-            Op::Square
-        } else if let Some(v) = op.strip_prefix("* ") {
-            Op::Multiply(str::parse::<usize>(v).map_err(|_| "invalid mult")?)
-        } else if let Some(v) = op.strip_prefix("+ ") {
-            Op::Add(str::parse::<usize>(v).map_err(|_| "invalid add")?)
-        } else {
-            return Err("Invalid operation");
-        }
-    };
+    let operation =
+        Expr::try_from(strip_words(operation, &["Operation:", "new", "="]).ok_or("No op")?)?;
     let parse_trailing_int =
         |line: &str, prefix, invalid_prefix_error, parse_error| -> Result<usize, &'static str> {
-            line.strip_prefix(prefix)
+            strip_words(line, prefix)
                 .ok_or(invalid_prefix_error)?
                 .parse::<usize>()
                 .map_err(|_| parse_error)
         };
     let divisible_by = parse_trailing_int(
-        &value[3],
-        "  Test: divisible by ",
+        test,
+        &["Test:", "divisible", "by"],
         "Invalid test",
         "Invalid divisor",
     )?;
+    if divisible_by == 0 {
+        return Err("Invalid divisor");
+    }
     let if_true = parse_trailing_int(
-        &value[4],
-        "    If true: throw to monkey ",
+        if_true,
+        &["If", "true:", "throw", "to", "monkey"],
         "Invalid if_true",
         "Invalid monkey",
     )?;
     let if_false = parse_trailing_int(
-        &value[5],
-        "    If false: throw to monkey ",
+        if_false,
+        &["If", "false:", "throw", "to", "monkey"],
         "Invalid if_false",
         "Invalid monkey",
     )?;
     Ok((
+        index,
         Monkey {
             operation,
             action: Action {
@@ -118,63 +143,506 @@ fn parse_monkey(value: [String; 7]) -> Result<(Monkey, VecDeque<Item>), &'static
     ))
 }
 
-fn run_one_round(
-    monkeys: &[Monkey],
-    starting_items: &mut [VecDeque<Item>],
+/// Parses the monkeys, separated by blank lines, and sorts them by index.
+fn parse_monkeys(input: &str) -> Result<(Vec<Monkey>, Vec<VecDeque<Item>>), &'static str> {
+    let lines = input.lines().collect::<Vec<_>>();
+    let mut parsed = lines
+        .split(|line| line.trim().is_empty())
+        .filter(|block| !block.is_empty())
+        .map(parse_monkey)
+        .collect::<Result<Vec<_>, _>>()?;
+    parsed.sort_by_key(|(index, _, _)| *index);
+    if parsed
+        .iter()
+        .enumerate()
+        .any(|(i, (index, _, _))| i != *index)
+    {
+        return Err("Monkey indices must be 0..N without duplicates");
+    }
+    for (index, monkey, _) in &parsed {
+        for target in [monkey.action.if_true, monkey.action.if_false] {
+            if target >= parsed.len() {
+                return Err("Throw to a monkey that doesn't exist");
+            }
+            if target == *index {
+                return Err("A monkey can't throw to itself");
+            }
+        }
+    }
+    Ok(parsed
+        .into_iter()
+        .map(|(_, monkey, items)| (monkey, items))
+        .unzip())
+}
+
+/// Plays one round, `throw` being called with the index of the monkey inspecting the item.
+fn run_one_round<T, E>(
+    starting_items: &mut [VecDeque<T>],
     items_inspected: &mut [usize],
-    common_divisor: usize,
-    worried: bool,
-) {
-    for i in 0..monkeys.len() {
-        while let Some(Throw { item, to }) = starting_items[i]
-            .pop_front()
-            .map(|item| monkeys[i].throw_one_item(item, common_divisor, worried))
-        {
+    throw: &mut impl FnMut(usize, T) -> Result<Throw<T>, E>,
+) -> Result<(), E> {
+    for i in 0..starting_items.len() {
+        while let Some(item) = starting_items[i].pop_front() {
+            let Throw { item, to } = throw(i, item)?;
             items_inspected[i] += 1;
             starting_items[to].push_back(item);
         }
     }
+    Ok(())
+}
+
+struct RoundsReport<T = Item> {
+    /// Number of items inspected by each monkey.
+    items_inspected: Vec<usize>,
+    /// Items held by each monkey after each of the requested rounds.
+    holdings: Vec<(usize, Vec<VecDeque<T>>)>,
+}
+
+impl<T: std::fmt::Display> RoundsReport<T> {
+    fn print(&self) {
+        for (round, items) in &self.holdings {
+            println!(
+                "After round {}, the monkeys are holding items with these worry levels:",
+                round
+            );
+            for (i, items) in items.iter().enumerate() {
+                let items = items.iter().map(T::to_string).collect::<Vec<_>>();
+                println!("Monkey {}: {}", i, items.join(", "));
+            }
+            println!();
+        }
+        for (i, count) in self.items_inspected.iter().enumerate() {
+            println!("Monkey {} inspected items {} times.", i, count);
+        }
+    }
+}
+
+/// Runs the rounds, keeping a copy of the items held after each round in `report_after`.
+fn run_rounds<T: Clone, E>(
+    mut starting_items: Vec<VecDeque<T>>,
+    num_rounds: usize,
+    report_after: &[usize],
+    mut throw: impl FnMut(usize, T) -> Result<Throw<T>, E>,
+) -> Result<RoundsReport<T>, E> {
+    let mut items_inspected = vec![0; starting_items.len()];
+    let mut holdings = Vec::new();
+    for round in 1..=num_rounds {
+        run_one_round(&mut starting_items, &mut items_inspected, &mut throw)?;
+        if report_after.contains(&round) {
+            holdings.push((round, starting_items.clone()));
+        }
+    }
+    Ok(RoundsReport {
+        items_inspected,
+        holdings,
+    })
 }
 
 fn run_all_rounds(
     monkeys: &[Monkey],
-    mut starting_items: Vec<VecDeque<Item>>,
+    starting_items: Vec<VecDeque<Item>>,
     num_rounds: usize,
     common_divisor: usize,
-    worried: bool,
-) -> usize {
-    let mut items_inspected = vec![0; monkeys.len()];
-    for _ in 0..num_rounds {
-        run_one_round(
-            monkeys,
-            &mut starting_items,
-            &mut items_inspected,
-            common_divisor,
-            worried,
-        );
-    }
+    relief: Item,
+    report_after: &[usize],
+) -> RoundsReport {
+    run_rounds(starting_items, num_rounds, report_after, |i, item| {
+        Ok::<_, std::convert::Infallible>(monkeys[i].throw_one_item(item, common_divisor, relief))
+    })
+    .unwrap_or_else(|never| match never {})
+}
+
+/// Same as `run_all_rounds`, but with exact worry levels: they grow very quickly, so this is
+/// only practical for a small number of rounds. Fails if a worry level would become negative.
+fn run_all_rounds_exact(
+    monkeys: &[Monkey],
+    starting_items: &[VecDeque<Item>],
+    num_rounds: usize,
+    relief: Item,
+    report_after: &[usize],
+) -> Result<RoundsReport<BigUint>, &'static str> {
+    let starting_items = starting_items
+        .iter()
+        .map(|items| {
+            items
+                .iter()
+                .map(|&item| BigUint::from(item as u64))
+                .collect()
+        })
+        .collect();
+    run_rounds(starting_items, num_rounds, report_after, |i, item| {
+        monkeys[i].throw_one_item_exact(&item, relief)
+    })
+}
+
+/// First round after which the worry levels of the modular simulation are not the remainders
+/// of the exact ones. Both reports must hold the same rounds.
+fn first_divergence(
+    exact: &RoundsReport<BigUint>,
+    modular: &RoundsReport,
+    common_divisor: usize,
+) -> Option<usize> {
+    exact
+        .holdings
+        .iter()
+        .zip(&modular.holdings)
+        .find(|((_, exact), (_, modular))| {
+            exact.iter().zip(modular).any(|(exact, modular)| {
+                exact.len() != modular.len()
+                    || exact
+                        .iter()
+                        .zip(modular)
+                        .any(|(e, &m)| e.div_rem_small(common_divisor as u64).1 != m as u64)
+            })
+        })
+        .map(|((round, _), _)| *round)
+}
+
+/// Product of the `top` highest inspection counts.
+fn monkey_business(mut items_inspected: Vec<usize>, top: usize) -> u128 {
     items_inspected.sort();
-    items_inspected[items_inspected.len() - 1] * items_inspected[items_inspected.len() - 2]
+    items_inspected
+        .iter()
+        .rev()
+        .take(top)
+        .map(|&c| c as u128)
+        .product()
+}
+
+/// Follows a single item for one round, starting at monkey `from`: it keeps being thrown
+/// within the round as long as it goes to a monkey that hasn't played yet. Returns the monkey
+/// holding it at the end of the round, and its worry level.
+fn run_item_round(
+    monkeys: &[Monkey],
+    mut from: usize,
+    mut item: Item,
+    items_inspected: &mut [usize],
+    common_divisor: usize,
+    relief: Item,
+) -> (usize, Item) {
+    loop {
+        items_inspected[from] += 1;
+        let throw = monkeys[from].throw_one_item(item, common_divisor, relief);
+        item = throw.item;
+        if throw.to <= from {
+            return (throw.to, item);
+        }
+        from = throw.to;
+    }
 }
 
+/// Same as `run_all_rounds`, but follows each item on its own: the inspection counts don't
+/// depend on the order in which items are held, and since worry levels are bounded by the
+/// common divisor, the state of an item at the start of a round eventually repeats. Once it
+/// does, the remaining rounds are extrapolated from the cycle.
+fn run_all_rounds_by_item(
+    monkeys: &[Monkey],
+    starting_items: &[VecDeque<Item>],
+    num_rounds: usize,
+    common_divisor: usize,
+    relief: Item,
+) -> Vec<usize> {
+    let mut total_inspected = vec![0; monkeys.len()];
+    for (start, &item) in starting_items
+        .iter()
+        .enumerate()
+        .flat_map(|(i, items)| items.iter().map(move |item| (i, item)))
+    {
+        // history[r] is the number of inspections by each monkey during the first r rounds.
+        let mut history = vec![vec![0; monkeys.len()]];
+        let mut seen = HashMap::new();
+        let mut state = (start, item);
+        let mut round = 0;
+        let inspected = loop {
+            if round == num_rounds {
+                break history.pop().unwrap();
+            }
+            if let Some(cycle_start) = seen.insert(state, round) {
+                let cycle_length = round - cycle_start;
+                let num_cycles = (num_rounds - cycle_start) / cycle_length;
+                let remainder = (num_rounds - cycle_start) % cycle_length;
+                break (0..monkeys.len())
+                    .map(|m| {
+                        history[cycle_start][m]
+                            + num_cycles * (history[round][m] - history[cycle_start][m])
+                            + (history[cycle_start + remainder][m] - history[cycle_start][m])
+                    })
+                    .collect();
+            }
+            let mut inspected = history[round].clone();
+            state = run_item_round(
+                monkeys,
+                state.0,
+                state.1,
+                &mut inspected,
+                common_divisor,
+                relief,
+            );
+            history.push(inspected);
+            round += 1;
+        };
+        for (total, count) in total_inspected.iter_mut().zip(inspected) {
+            *total += count;
+        }
+    }
+    total_inspected
+}
+
+const USAGE: &str =
+    "Usage: day11 [report | fast | exact] [--rounds <rounds>] [--relief <divisor>] \
+                     [--top <count>] [--after <round>,<round>...]";
+
 fn main() {
-    let (monkeys, starting_items): (Vec<_>, Vec<_>) = std::io::stdin()
-        .lines()
-        .map(Result::unwrap)
-        .array_chunks::<7>()
-        .map(parse_monkey)
-        .map(Result::unwrap)
-        .unzip();
+    let mut mode = None;
+    let mut num_rounds = None;
+    let mut relief = None;
+    let mut top = 2;
+    let mut report_after = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect(USAGE);
+        let parse = |v: &str| v.parse::<usize>().expect("Invalid number");
+        match arg.as_str() {
+            "report" | "fast" | "exact" => mode = Some(arg),
+            "--rounds" => num_rounds = Some(parse(&value())),
+            "--relief" => relief = Some(parse(&value())),
+            "--top" => top = parse(&value()),
+            "--after" => report_after = value().split(',').map(parse).collect(),
+            _ => panic!("{}", USAGE),
+        }
+    }
+    assert!(
+        mode.is_some() || (num_rounds.is_none() && relief.is_none() && report_after.is_empty()),
+        "--rounds, --relief and --after need a mode\n{}",
+        USAGE
+    );
+    assert!(
+        mode.as_deref() != Some("fast") || report_after.is_empty(),
+        "The fast mode doesn't report holdings"
+    );
+    assert!(relief != Some(0), "The relief must be positive");
+    let (monkeys, starting_items) = {
+        let mut contents = String::new();
+        use std::io::Read;
+        std::io::stdin().read_to_string(&mut contents).unwrap();
+        parse_monkeys(&contents).unwrap()
+    };
     let common_divisor = monkeys
         .iter()
         .map(|m| m.action.divisible_by)
         .product::<usize>();
-    println!(
-        "{}",
-        run_all_rounds(&monkeys, starting_items.clone(), 20, common_divisor, false)
-    );
-    println!(
-        "{}",
-        run_all_rounds(&monkeys, starting_items, 10000, common_divisor, true)
-    );
+    match mode.as_deref() {
+        None => {
+            for (num_rounds, relief) in [(20, 3), (10000, 1)] {
+                let report = run_all_rounds(
+                    &monkeys,
+                    starting_items.clone(),
+                    num_rounds,
+                    common_divisor,
+                    relief,
+                    &[],
+                );
+                println!("{}", monkey_business(report.items_inspected, top));
+            }
+        }
+        Some("report") => {
+            let report = run_all_rounds(
+                &monkeys,
+                starting_items,
+                num_rounds.unwrap_or(20),
+                common_divisor,
+                relief.unwrap_or(3),
+                &report_after,
+            );
+            report.print();
+            println!(
+                "Monkey business: {}",
+                monkey_business(report.items_inspected, top)
+            );
+        }
+        Some("exact") => {
+            let num_rounds = num_rounds.unwrap_or(20);
+            let relief = relief.unwrap_or(3);
+            let all_rounds = (1..=num_rounds).collect::<Vec<_>>();
+            let mut exact =
+                run_all_rounds_exact(&monkeys, &starting_items, num_rounds, relief, &all_rounds)
+                    .unwrap();
+            let modular = run_all_rounds(
+                &monkeys,
+                starting_items,
+                num_rounds,
+                common_divisor,
+                relief,
+                &all_rounds,
+            );
+            let divergence = first_divergence(&exact, &modular, common_divisor);
+            exact
+                .holdings
+                .retain(|(round, _)| report_after.contains(round));
+            exact.print();
+            match divergence {
+                Some(round) => println!("The modular mode diverges after round {}", round),
+                None if exact.items_inspected != modular.items_inspected => {
+                    println!("The modular mode counts different inspections")
+                }
+                None => println!("The modular mode agrees with the exact worry levels"),
+            }
+            println!(
+                "Monkey business: {}",
+                monkey_business(exact.items_inspected, top)
+            );
+        }
+        Some(_) => {
+            let items_inspected = run_all_rounds_by_item(
+                &monkeys,
+                &starting_items,
+                num_rounds.unwrap_or(10000),
+                common_divisor,
+                relief.unwrap_or(1),
+            );
+            println!("{}", monkey_business(items_inspected, top));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1
+
+";
+
+    fn parse_example() -> (Vec<Monkey>, Vec<VecDeque<Item>>) {
+        parse_monkeys(EXAMPLE).unwrap()
+    }
+
+    #[test]
+    fn test_run_by_item_matches_simulation() {
+        let (monkeys, starting_items) = parse_example();
+        let common_divisor = 23 * 19 * 13 * 17;
+        for (rounds, relief) in [(20, 3), (20, 1), (1000, 1), (10000, 1)] {
+            assert_eq!(
+                run_all_rounds_by_item(&monkeys, &starting_items, rounds, common_divisor, relief),
+                run_all_rounds(
+                    &monkeys,
+                    starting_items.clone(),
+                    rounds,
+                    common_divisor,
+                    relief,
+                    &[]
+                )
+                .items_inspected
+            );
+        }
+    }
+
+    #[test]
+    fn test_exact_worry_levels() {
+        let (monkeys, starting_items) = parse_example();
+        let common_divisor = 23 * 19 * 13 * 17;
+        let exact = run_all_rounds_exact(&monkeys, &starting_items, 20, 3, &[1]).unwrap();
+        assert_eq!(exact.items_inspected, [101, 95, 7, 105]);
+        let first_round = exact.holdings[0].1[0].iter().map(BigUint::to_string);
+        assert_eq!(first_round.collect::<Vec<_>>(), ["20", "23", "27", "26"]);
+
+        let rounds = (1..=20).collect::<Vec<_>>();
+        let exact = run_all_rounds_exact(&monkeys, &starting_items, 20, 1, &rounds).unwrap();
+        let modular = run_all_rounds(&monkeys, starting_items, 20, common_divisor, 1, &rounds);
+        assert_eq!(exact.items_inspected, [99, 97, 8, 103]);
+        assert_eq!(exact.items_inspected, modular.items_inspected);
+        assert_eq!(first_divergence(&exact, &modular, common_divisor), None);
+
+        let big = BigUint::from(u64::MAX).mul(&BigUint::from(1_000_000_000));
+        assert_eq!(big.to_string(), "18446744073709551615000000000");
+        assert_eq!(
+            big.div_rem_small(1_000_000_000),
+            (BigUint::from(u64::MAX), 0)
+        );
+    }
+
+    #[test]
+    fn test_operations() {
+        let eval = |op: &str, old| Expr::try_from(op).unwrap().evaluate(old, 100);
+        assert_eq!(eval("old * old", 7), 49);
+        assert_eq!(eval("old * 19", 2), 38);
+        assert_eq!(eval("3 + old", 2), 5);
+        assert_eq!(eval("old - 5", 7), 2);
+        assert_eq!(eval("5 - old", 7), 98);
+        assert_eq!(eval("2 * old + 1 * 3", 5), 13);
+        assert_eq!(eval("(old - 2) * old", 5), 15);
+        // Real-size divisor: the cube of a reduced worry level doesn't fit in 64 bits.
+        let common_divisor = 2 * 3 * 5 * 7 * 11 * 13 * 17 * 19;
+        let old = common_divisor - 1;
+        let cube = Expr::try_from("old * old * old").unwrap();
+        assert_eq!(cube.evaluate(old, common_divisor), common_divisor - 1);
+        assert_eq!(cube.evaluate(9_000_000, common_divisor), 5_307_030);
+        assert_eq!(
+            Expr::try_from("old * old * old + old * old")
+                .unwrap()
+                .evaluate(old, common_divisor),
+            0
+        );
+        assert!(Expr::try_from("old / 2").is_err());
+        assert!(Expr::try_from("(old + 1").is_err());
+        assert!(Expr::try_from("old old").is_err());
+    }
+
+    #[test]
+    fn test_parse_monkeys() {
+        let input = "Monkey 1:
+  Starting items:
+  Operation: new = old+3
+  Test: divisible by 5
+    If true: throw to monkey 0
+    If false: throw to monkey 0
+
+
+  Monkey 0:  \t
+Starting  items: 4 ,5
+      Operation:   new =   old *  old
+  Test: divisible by 2
+    If true:  throw to monkey 1
+    If false: throw to monkey 1";
+        let (monkeys, items) = parse_monkeys(input).unwrap();
+        assert_eq!(monkeys.len(), 2);
+        assert_eq!(items, vec![VecDeque::from([4, 5]), VecDeque::new()]);
+        assert_eq!(monkeys[0].action.divisible_by, 2);
+        assert_eq!(monkeys[1].operation.evaluate(1, 5), 4);
+        assert_eq!(
+            parse_monkeys(&input.replace("Monkey 0:", "Monkey 2:")).err(),
+            Some("Monkey indices must be 0..N without duplicates")
+        );
+        assert_eq!(
+            parse_monkeys(&input.replace("throw to monkey 1", "throw to monkey 3")).err(),
+            Some("Throw to a monkey that doesn't exist")
+        );
+    }
 }
@@ -81,17 +81,88 @@ impl Default for Cell {
     }
 }
 
-fn can_step(from: u8, to: u8) -> bool {
-    if from == b'S' {
-        to == b'a' || to == b'b'
-    } else if to == b'E' {
-        from == b'y' || from == b'z'
-    } else {
-        to as i16 - from as i16 <= 1
+/// The start is at elevation `a` and the end at elevation `z`.
+fn elevation(cell: u8) -> i16 {
+    match cell {
+        b'S' => b'a' as i16,
+        b'E' => b'z' as i16,
+        c => c as i16,
     }
 }
 
-fn run_dijkstra(input_grid: &Grid<u8>, start: (i64, i64), reset_at_a: bool) -> Option<usize> {
+#[derive(Clone, Copy)]
+enum Neighborhood {
+    Four,
+    Eight,
+}
+
+impl Neighborhood {
+    fn offsets(self) -> &'static [(i64, i64)] {
+        const CARDINALS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        const ALL_DIRECTIONS: [(i64, i64); 8] = [
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (1, -1),
+            (-1, 1),
+            (-1, -1),
+        ];
+        match self {
+            Neighborhood::Four => &CARDINALS,
+            Neighborhood::Eight => &ALL_DIRECTIONS,
+        }
+    }
+}
+
+/// Which steps are allowed, and what they cost: `step_cost`, plus `ascent_cost` per level
+/// climbed or `descent_cost` per level descended.
+#[derive(Clone, Copy)]
+struct ClimbingRules {
+    max_ascent: i16,
+    max_descent: i16,
+    neighborhood: Neighborhood,
+    step_cost: usize,
+    ascent_cost: usize,
+    descent_cost: usize,
+}
+
+impl Default for ClimbingRules {
+    /// Climb at most one level, descend any amount, every step costs 1.
+    fn default() -> Self {
+        Self {
+            max_ascent: 1,
+            max_descent: i16::MAX,
+            neighborhood: Neighborhood::Four,
+            step_cost: 1,
+            ascent_cost: 0,
+            descent_cost: 0,
+        }
+    }
+}
+
+impl ClimbingRules {
+    /// Cost of the step, or `None` if it isn't allowed.
+    fn step_cost(&self, from: u8, to: u8) -> Option<usize> {
+        let climb = elevation(to) - elevation(from);
+        if climb > self.max_ascent || -climb > self.max_descent {
+            return None;
+        }
+        Some(if climb >= 0 {
+            self.step_cost + self.ascent_cost * climb as usize
+        } else {
+            self.step_cost + self.descent_cost * (-climb) as usize
+        })
+    }
+}
+
+fn run_dijkstra(
+    input_grid: &Grid<u8>,
+    start: (i64, i64),
+    reset_at_a: bool,
+    rules: &ClimbingRules,
+) -> Option<usize> {
     let mut dijkstra_grid = Grid::<CellValue>::new(input_grid.height, input_grid.width);
     dijkstra_grid[start].distance = 0;
     let mut next_cells = std::collections::BTreeSet::<Cell>::new();
@@ -99,7 +170,6 @@ fn run_dijkstra(input_grid: &Grid<u8>, start: (i64, i64), reset_at_a: bool) -> O
         coords: start,
         distance: 0,
     });
-    const CARDINALS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
     while let Some(c) = next_cells.pop_first() {
         if input_grid[c.coords] == b'E' {
             return Some(c.distance);
@@ -109,7 +179,7 @@ fn run_dijkstra(input_grid: &Grid<u8>, start: (i64, i64), reset_at_a: bool) -> O
         }
         assert_eq!(dijkstra_grid[c.coords].distance, c.distance);
         dijkstra_grid[c.coords].visited = true;
-        for (dx, dy) in CARDINALS {
+        for &(dx, dy) in rules.neighborhood.offsets() {
             let new_coords = (c.coords.0 + dx, c.coords.1 + dy);
             if new_coords.0 < 0
                 || new_coords.1 < 0
@@ -118,14 +188,15 @@ fn run_dijkstra(input_grid: &Grid<u8>, start: (i64, i64), reset_at_a: bool) -> O
             {
                 continue;
             }
+            let Some(cost) = rules.step_cost(input_grid[c.coords], input_grid[new_coords]) else {
+                continue;
+            };
             let new_distance = if reset_at_a && input_grid[new_coords] == b'a' {
                 0
             } else {
-                c.distance + 1
+                c.distance + cost
             };
-            if !can_step(input_grid[c.coords], input_grid[new_coords])
-                || dijkstra_grid[new_coords].distance <= new_distance
-            {
+            if dijkstra_grid[new_coords].distance <= new_distance {
                 continue;
             }
             dijkstra_grid[new_coords].distance = new_distance;
@@ -138,7 +209,24 @@ fn run_dijkstra(input_grid: &Grid<u8>, start: (i64, i64), reset_at_a: bool) -> O
     None
 }
 
+const USAGE: &str = "Usage: day12 [--ascent <levels>] [--descent <levels>] [--diagonal] \
+                     [--step-cost <cost>] [--ascent-cost <cost>] [--descent-cost <cost>]";
+
 fn main() {
+    let mut rules = ClimbingRules::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect(USAGE);
+        match arg.as_str() {
+            "--ascent" => rules.max_ascent = value().parse().expect("Invalid ascent"),
+            "--descent" => rules.max_descent = value().parse().expect("Invalid descent"),
+            "--diagonal" => rules.neighborhood = Neighborhood::Eight,
+            "--step-cost" => rules.step_cost = value().parse().expect("Invalid cost"),
+            "--ascent-cost" => rules.ascent_cost = value().parse().expect("Invalid cost"),
+            "--descent-cost" => rules.descent_cost = value().parse().expect("Invalid cost"),
+            _ => panic!("{}", USAGE),
+        }
+    }
     let input_grid =
         Grid::<u8>::from_iterator(std::io::stdin().lines().map(std::result::Result::unwrap))
             .unwrap();
@@ -152,6 +240,43 @@ fn main() {
         }
         unreachable!();
     })();
-    println!("{}", run_dijkstra(&input_grid, start, false).unwrap());
-    println!("{}", run_dijkstra(&input_grid, start, true).unwrap());
+    println!(
+        "{}",
+        run_dijkstra(&input_grid, start, false, &rules).unwrap()
+    );
+    println!(
+        "{}",
+        run_dijkstra(&input_grid, start, true, &rules).unwrap()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [&str; 5] = ["Sabqponm", "abcryxxl", "accszExk", "acctuvwj", "abdefghi"];
+
+    #[test]
+    fn test_climbing_rules() {
+        let grid = Grid::<u8>::from_iterator(EXAMPLE.iter()).unwrap();
+        let rules = ClimbingRules::default();
+        assert_eq!(run_dijkstra(&grid, (0, 0), false, &rules), Some(31));
+        assert_eq!(run_dijkstra(&grid, (0, 0), true, &rules), Some(29));
+        // Climbing 25 levels costs 25 more, whatever the route.
+        let weighted = ClimbingRules {
+            ascent_cost: 1,
+            ..rules
+        };
+        assert_eq!(run_dijkstra(&grid, (0, 0), false, &weighted), Some(56));
+        let diagonal = ClimbingRules {
+            neighborhood: Neighborhood::Eight,
+            ..rules
+        };
+        assert_eq!(run_dijkstra(&grid, (0, 0), false, &diagonal), Some(27));
+        let flat = ClimbingRules {
+            max_ascent: 0,
+            ..rules
+        };
+        assert_eq!(run_dijkstra(&grid, (0, 0), false, &flat), None);
+    }
 }
@@ -1,85 +1,335 @@
 #![feature(iter_array_chunks)]
 
-type Int = u8;
+use std::cmp::Ordering;
 
-#[derive(PartialEq, Eq, Clone)]
+/// Non-negative integer of any size, stored as its decimal digits without leading zeros.
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Int(Box<str>);
+
+impl Int {
+    /// `digits` must be non-empty ASCII digits.
+    fn from_digits(digits: &str) -> Self {
+        let trimmed = digits.trim_start_matches('0');
+        Self(if trimmed.is_empty() { "0" } else { trimmed }.into())
+    }
+}
+
+impl From<u64> for Int {
+    fn from(value: u64) -> Self {
+        Self(value.to_string().into())
+    }
+}
+
+impl PartialOrd for Int {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Int {
+    /// Without leading zeros, the longer number is the larger one.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl std::fmt::Display for Int {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
 enum Node {
     Int(Int),
     List(Vec<Node>),
 }
 
 impl PartialOrd for Node {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
 impl Ord for Node {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        use std::cmp::Ordering;
-        match (self, other) {
-            (Node::Int(a), Node::Int(b)) => a.cmp(b),
-            (Node::Int(_), Node::List(l)) if l.is_empty() => Ordering::Greater,
-            (Node::Int(a), Node::List(l)) => [Node::Int(*a)].as_slice().cmp(l.as_slice()),
-            (Node::List(l), Node::Int(_)) if l.is_empty() => Ordering::Less,
-            (Node::List(l), Node::Int(b)) => l.as_slice().cmp([Node::Int(*b)].as_slice()),
-            (Node::List(l1), Node::List(l2)) => l1.cmp(l2),
-        }
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_with(self, other, 0, &mut |_, _| {})
     }
 }
 
-impl<'a> TryFrom<&'a str> for Node {
-    type Error = &'static str;
-    fn try_from(line: &'a str) -> Result<Self, Self::Error> {
-        fn parse_one_node(mut line: &[u8]) -> Result<(Node, &[u8]), &'static str> {
-            if line.is_empty() {
-                return Err("Input too short");
+/// One step of the comparison of two packets.
+enum CompareEvent<'a> {
+    Compare(&'a Node, &'a Node),
+    /// The integer on one side is converted to a list, `left` telling which side.
+    Convert {
+        left: bool,
+        value: &'a Int,
+    },
+    /// Two integers differ.
+    Decided(Ordering),
+    /// One of the lists ran out of items before a difference was found.
+    RanOut(Ordering),
+}
+
+/// Compares two packets, reporting each step of the comparison with its nesting depth.
+fn compare_with<F: FnMut(usize, CompareEvent<'_>)>(
+    left: &Node,
+    right: &Node,
+    depth: usize,
+    on_event: &mut F,
+) -> Ordering {
+    on_event(depth, CompareEvent::Compare(left, right));
+    match (left, right) {
+        (Node::Int(a), Node::Int(b)) => {
+            let ordering = a.cmp(b);
+            if ordering.is_ne() {
+                on_event(depth + 1, CompareEvent::Decided(ordering));
+            }
+            ordering
+        }
+        (Node::Int(a), Node::List(_)) => {
+            on_event(
+                depth + 1,
+                CompareEvent::Convert {
+                    left: true,
+                    value: a,
+                },
+            );
+            compare_with(&Node::List(vec![left.clone()]), right, depth + 1, on_event)
+        }
+        (Node::List(_), Node::Int(b)) => {
+            on_event(
+                depth + 1,
+                CompareEvent::Convert {
+                    left: false,
+                    value: b,
+                },
+            );
+            compare_with(left, &Node::List(vec![right.clone()]), depth + 1, on_event)
+        }
+        (Node::List(l1), Node::List(l2)) => {
+            for (a, b) in l1.iter().zip(l2) {
+                let ordering = compare_with(a, b, depth + 1, on_event);
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+            let ordering = l1.len().cmp(&l2.len());
+            if ordering.is_ne() {
+                on_event(depth + 1, CompareEvent::RanOut(ordering));
             }
-            if line[0] == b'[' {
-                if line[1] == b']' {
-                    return Ok((Node::List(vec![]), &line[2..]));
+            ordering
+        }
+    }
+}
+
+/// Explains the comparison of two packets the way the puzzle does.
+fn explain(left: &Node, right: &Node) -> String {
+    let verdict = |ordering: Ordering| {
+        if ordering.is_lt() {
+            "so inputs are in the right order"
+        } else {
+            "so inputs are not in the right order"
+        }
+    };
+    let mut lines = Vec::new();
+    compare_with(left, right, 0, &mut |depth, event| {
+        let line = match event {
+            CompareEvent::Compare(l, r) => format!("Compare {} vs {}", l, r),
+            CompareEvent::Convert { left, value } => format!(
+                "Mixed types; convert {} to [{}] and retry comparison",
+                if left { "left" } else { "right" },
+                value
+            ),
+            CompareEvent::Decided(ordering) => format!(
+                "{} side is smaller, {}",
+                if ordering.is_lt() { "Left" } else { "Right" },
+                verdict(ordering)
+            ),
+            CompareEvent::RanOut(ordering) => format!(
+                "{} side ran out of items, {}",
+                if ordering.is_lt() { "Left" } else { "Right" },
+                verdict(ordering)
+            ),
+        };
+        lines.push(format!("{}- {}", "  ".repeat(depth), line));
+    });
+    lines.join("\n")
+}
+
+/// Writes the node back in the bracket syntax, without whitespace.
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Node::Int(n) => write!(f, "{}", n),
+            Node::List(nodes) => {
+                write!(f, "[")?;
+                for (i, node) in nodes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", node)?;
                 }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ParseError {
+    /// Byte offset in the input where parsing failed.
+    offset: usize,
+    message: &'static str,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    /// Skips whitespace and returns the next byte without consuming it.
+    fn peek(&mut self) -> Option<u8> {
+        let bytes = self.input.as_bytes();
+        while self.pos < bytes.len() && bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        bytes.get(self.pos).copied()
+    }
+
+    /// node := int | '[' (node (',' node)*)? ']'
+    fn node(&mut self) -> Result<Node, ParseError> {
+        match self.peek() {
+            Some(b'[') => {
+                self.pos += 1;
                 let mut nodes = vec![];
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Node::List(nodes));
+                }
                 loop {
-                    let (node, rest) = parse_one_node(&line[1..])?;
-                    nodes.push(node);
-                    if rest[0] == b',' {
-                        line = &rest[0..];
-                    } else if rest[0] == b']' {
-                        break Ok((Node::List(nodes), &rest[1..]));
-                    } else {
-                        break Err("Missing closing delimiter");
+                    nodes.push(self.node()?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Node::List(nodes));
+                        }
+                        Some(_) => return Err(self.error("Expected ',' or ']'")),
+                        None => return Err(self.error("Missing closing delimiter")),
                     }
                 }
-            } else if line[0].is_ascii_digit() {
-                let mut num = 0;
-                let mut i = 0;
-                while i < line.len() && line[i].is_ascii_digit() {
-                    num *= 10;
-                    num += line[i] - b'0';
-                    i += 1;
-                }
-                Ok((Node::Int(num), &line[i..]))
-            } else {
-                Err("Unexpected input")
             }
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                let len = self.input[start..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(self.input.len() - start);
+                self.pos += len;
+                Ok(Node::Int(Int::from_digits(&self.input[start..self.pos])))
+            }
+            Some(_) => Err(self.error("Expected an integer or a list")),
+            None => Err(self.error("Unexpected end of input")),
         }
-        let (node, rest) = parse_one_node(line.as_bytes())?;
-        if rest.is_empty() {
-            Ok(node)
-        } else {
-            Err("Leftover input")
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Node {
+    type Error = ParseError;
+    fn try_from(line: &'a str) -> Result<Self, Self::Error> {
+        let mut parser = Parser {
+            input: line,
+            pos: 0,
+        };
+        let node = parser.node()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("Leftover input"));
         }
+        Ok(node)
     }
 }
 
+/// 1-based position of each divider once the packets and the dividers are stably sorted, with
+/// the dividers after the packets: O(n) comparisons per divider, without sorting anything.
+fn divider_ranks(packets: &[Node], dividers: &[Node]) -> Vec<usize> {
+    dividers
+        .iter()
+        .enumerate()
+        .map(|(i, divider)| {
+            let packets_before = packets.iter().filter(|p| *p <= divider).count();
+            let dividers_before = dividers
+                .iter()
+                .enumerate()
+                .filter(|&(j, d)| if j < i { d <= divider } else { d < divider })
+                .count();
+            packets_before + dividers_before + 1
+        })
+        .collect()
+}
+
+const USAGE: &str = "Usage: day13 [explain | sorted] [--divider <packet>]...";
+
 fn main() {
-    let mut nodes = std::io::stdin()
+    let mut mode = None;
+    let mut dividers = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "explain" | "sorted" => mode = Some(arg),
+            "--divider" => {
+                let packet = args.next().expect(USAGE);
+                dividers.push(
+                    Node::try_from(packet.as_str()).unwrap_or_else(|e| panic!("{}: {}", e, packet)),
+                );
+            }
+            _ => panic!("{}", USAGE),
+        }
+    }
+    if dividers.is_empty() {
+        dividers = ["[[2]]", "[[6]]"]
+            .map(|d| Node::try_from(d).unwrap())
+            .into();
+    }
+    let nodes = std::io::stdin()
         .lines()
         .map(Result::unwrap)
-        .filter(|l| !l.is_empty())
-        .map(|s| Node::try_from(s.as_str()).unwrap())
+        .filter(|l| !l.trim().is_empty())
+        .map(|s| Node::try_from(s.as_str()).unwrap_or_else(|e| panic!("{}: {}", e, s)))
         .collect::<Vec<_>>();
+    match mode.as_deref() {
+        Some("explain") => {
+            for (i, [left, right]) in nodes.iter().array_chunks::<2>().enumerate() {
+                println!("== Pair {} ==\n{}\n", i + 1, explain(left, right));
+            }
+            return;
+        }
+        Some(_) => {
+            let mut sorted = nodes.iter().chain(&dividers).collect::<Vec<_>>();
+            sorted.sort();
+            for packet in sorted {
+                println!("{}", packet);
+            }
+            return;
+        }
+        None => {}
+    }
     println!(
         "{}",
         nodes
@@ -93,12 +343,92 @@ fn main() {
             })
             .sum::<usize>()
     );
-    let delim_1 = Node::List(vec![Node::List(vec![Node::Int(2)])]);
-    let delim_2 = Node::List(vec![Node::List(vec![Node::Int(6)])]);
-    nodes.push(delim_1.clone());
-    nodes.push(delim_2.clone());
-    nodes.sort_unstable();
-    let pos_1 = nodes.binary_search(&delim_1).unwrap() + 1;
-    let pos_2 = nodes.binary_search(&delim_2).unwrap() + 1;
-    println!("{}", pos_1 * pos_2);
+    println!(
+        "{}",
+        divider_ranks(&nodes, &dividers)
+            .into_iter()
+            .product::<usize>()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        for packet in [
+            "[]",
+            "[[1],[2,3,4]]",
+            "[[[]],10,[7,[0]]]",
+            "[123456789012345678901234567890]",
+        ] {
+            assert_eq!(Node::try_from(packet).unwrap().to_string(), packet);
+        }
+        assert_eq!(
+            Node::try_from(" [ 1 , [ ] ,007 ] ").unwrap().to_string(),
+            "[1,[],7]"
+        );
+        let error = |message, offset| Err(ParseError { offset, message });
+        assert_eq!(Node::try_from("["), error("Unexpected end of input", 1));
+        assert_eq!(
+            Node::try_from("[1,2"),
+            error("Missing closing delimiter", 4)
+        );
+        assert_eq!(Node::try_from("[1;2]"), error("Expected ',' or ']'", 2));
+        assert_eq!(
+            Node::try_from("[1,]"),
+            error("Expected an integer or a list", 3)
+        );
+        assert_eq!(Node::try_from("[1] 2"), error("Leftover input", 4));
+        assert_eq!(Node::try_from(""), error("Unexpected end of input", 0));
+    }
+
+    #[test]
+    fn test_explain() {
+        let parse = |s| Node::try_from(s).unwrap();
+        assert_eq!(
+            explain(&parse("[[1],[2,3,4]]"), &parse("[[1],4]")),
+            "\
+- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order"
+        );
+        assert_eq!(
+            explain(&parse("[[[]]]"), &parse("[[]]")),
+            "\
+- Compare [[[]]] vs [[]]
+  - Compare [[]] vs []
+    - Right side ran out of items, so inputs are not in the right order"
+        );
+    }
+
+    #[test]
+    fn test_divider_ranks() {
+        let parse = |s| Node::try_from(s).unwrap();
+        let packets = ["[1,1,3,1,1]", "[[1],4]", "[9]", "[[2]]", "[]", "[[8,7,6]]"].map(parse);
+        let dividers = ["[[6]]", "[[2]]", "[2]", "[[2]]"].map(parse);
+        let mut sorted = packets.iter().chain(&dividers).collect::<Vec<_>>();
+        sorted.sort();
+        let expected = dividers
+            .iter()
+            .map(|d| sorted.iter().position(|p| std::ptr::eq(*p, d)).unwrap() + 1)
+            .collect::<Vec<_>>();
+        assert_eq!(divider_ranks(&packets, &dividers), expected);
+        assert_eq!(expected, [8, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_compare_large_integers() {
+        let parse = |s| Node::try_from(s).unwrap();
+        assert!(parse("[256]") > parse("[255]"));
+        assert!(parse("[99999999999999999999]") < parse("[100000000000000000000]"));
+        assert_eq!(parse("[[0010]]"), parse("[[10]]"));
+        assert!(parse("[9]") < parse("[[10]]"));
+    }
 }
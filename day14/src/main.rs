@@ -1,13 +1,18 @@
+mod render;
+
 use std::cmp::{max, min};
 
+use crate::render::Cave;
+
+#[derive(Clone)]
 struct Grid {
-    height: u8,
+    height: u16,
     width: u16,
     cells: Vec<u8>,
 }
 
 impl Grid {
-    fn new(height: u8, width: u16) -> Self {
+    fn new(height: u16, width: u16) -> Self {
         Self {
             height,
             width,
@@ -15,95 +20,479 @@ impl Grid {
         }
     }
 
-    fn is_full(&self, x: u16, y: u8) -> bool {
+    fn is_full(&self, x: u16, y: u16) -> bool {
         let linear_index = y as usize * self.width as usize + x as usize;
         self.cells[linear_index / 8] & (1 << (linear_index % 8)) != 0
     }
 
-    fn set_full(&mut self, x: u16, y: u8) {
+    fn set_full(&mut self, x: u16, y: u16) {
         let linear_index = y as usize * self.width as usize + x as usize;
         self.cells[linear_index / 8] |= 1 << (linear_index % 8)
     }
 }
 
-fn parse_line(line: String) -> Vec<(u16, u8)> {
-    line.split(" -> ")
-        .map(|c| {
-            let coords = c.split_once(',').unwrap();
-            (
-                coords.0.parse::<u16>().unwrap(),
-                coords.1.parse::<u8>().unwrap(),
-            )
-        })
-        .collect()
+fn parse_point(point: &str) -> (u16, u16) {
+    let coords = point.split_once(',').unwrap();
+    (
+        coords.0.trim().parse::<u16>().unwrap(),
+        coords.1.trim().parse::<u16>().unwrap(),
+    )
+}
+
+fn parse_line(line: String) -> Vec<(u16, u16)> {
+    line.split(" -> ").map(parse_point).collect()
 }
 
-fn populate_grid(grid: &mut Grid, rocks: &[Vec<(u16, u8)>], min_x: u16) {
+/// `min_x` is the leftmost column that sand or rocks can reach, which may be left of x = 0.
+fn populate_grid(grid: &mut Grid, rocks: &[Vec<(u16, u16)>], min_x: i32) {
+    let column = |x: u16| (x as i32 - min_x + 1) as u16;
     for rock in rocks.iter() {
         for line in rock.windows(2) {
             let from = line.first().unwrap();
             let to = line.last().unwrap();
             if from.0 == to.0 {
                 for y in min(from.1, to.1)..=max(from.1, to.1) {
-                    grid.set_full(from.0 - min_x + 1, y);
+                    grid.set_full(column(from.0), y);
                 }
             } else {
                 for x in min(from.0, to.0)..=max(from.0, to.0) {
-                    grid.set_full(x - min_x + 1, from.1);
+                    grid.set_full(column(x), from.1);
                 }
             }
         }
     }
 }
 
-fn fill_sand(grid: &mut Grid, start_x: u16, max_y: u8) -> (usize, usize) {
-    let mut stack = vec![start_x];
-    // The sand moves diagonaly, so we'll never get beyond one grain per line.
-    stack.reserve(grid.height.into());
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SandEvent {
+    /// A grain came to rest, with the number of grains at rest so far.
+    Rested(usize),
+    /// Without a floor, this grain would fall into the abyss: end of part 1.
+    ReachedAbyss(usize),
+    /// A grain came to rest at a source, which stops pouring sand.
+    SourceBlocked(usize),
+}
+
+/// Pours sand from all the sources in turn, one grain each, until the first grain falls past
+/// the lowest rock and, if there is a floor, until every source is blocked. Returns the number
+/// of grains at rest at both points.
+fn fill_sand(
+    grid: &mut Grid,
+    sources: &[(u16, u16)],
+    max_y: u16,
+    floor_y: Option<u16>,
+    on_event: &mut impl FnMut(&Grid, SandEvent),
+) -> (usize, Option<usize>) {
+    // The path of the last grain from each source, as one x per line below the source: the
+    // sand moves diagonaly, so we'll never get beyond one grain per line.
+    let mut paths = sources
+        .iter()
+        .map(|&(x, y)| {
+            let mut path = Vec::with_capacity((grid.height - y).into());
+            path.push(x);
+            (y, path)
+        })
+        .collect::<Vec<_>>();
     let mut sand_count = 0;
-    let mut first_sand_count = 0;
-    loop {
-        let x = *stack.last().unwrap();
-        let y = (stack.len() - 1) as u8;
-        if first_sand_count == 0 && y == max_y {
-            first_sand_count = sand_count;
+    let mut first_sand_count = None;
+    while !paths.iter().all(|(_, path)| path.is_empty()) {
+        for (source_y, stack) in &mut paths {
+            // Sand from the other sources may have landed on the path: the path is still
+            // followed up to the first full cell.
+            if sources.len() > 1 {
+                let full = stack
+                    .iter()
+                    .enumerate()
+                    .position(|(i, &x)| grid.is_full(x, *source_y + i as u16));
+                if let Some(full) = full {
+                    stack.truncate(full);
+                }
+            }
+            while let Some(&x) = stack.last() {
+                let y = *source_y + (stack.len() - 1) as u16;
+                if first_sand_count.is_none() && y == max_y {
+                    first_sand_count = Some(sand_count);
+                    on_event(grid, SandEvent::ReachedAbyss(sand_count));
+                    if floor_y.is_none() {
+                        return (sand_count, None);
+                    }
+                }
+                if Some(y + 1) == floor_y
+                    || grid.is_full(x, y + 1)
+                        && grid.is_full(x - 1, y + 1)
+                        && grid.is_full(x + 1, y + 1)
+                {
+                    grid.set_full(x, y);
+                    stack.truncate(stack.len() - 1);
+                    sand_count += 1;
+                    on_event(
+                        grid,
+                        if stack.is_empty() {
+                            SandEvent::SourceBlocked(sand_count)
+                        } else {
+                            SandEvent::Rested(sand_count)
+                        },
+                    );
+                    break;
+                } else if !grid.is_full(x, y + 1) {
+                    stack.push(x);
+                } else if !grid.is_full(x - 1, y + 1) {
+                    stack.push(x - 1);
+                } else {
+                    stack.push(x + 1);
+                }
+            }
         }
-        // Floor at max_y == 2.
-        if y == max_y + 1 {
-            grid.set_full(x, y);
-            stack.truncate(stack.len() - 1);
-            sand_count += 1;
-        } else if !grid.is_full(x, y + 1) {
-            stack.push(x);
-        } else if !grid.is_full(x - 1, y + 1) {
-            stack.push(x - 1);
-        } else if !grid.is_full(x + 1, y + 1) {
-            stack.push(x + 1);
-        // Sand coming to rest at (500, 0).
-        } else if stack.len() == 1 {
-            return (first_sand_count, sand_count + 1);
-        } else {
-            grid.set_full(x, y);
-            stack.truncate(stack.len() - 1);
-            sand_count += 1;
+    }
+    // Without a floor there is no part 2, even if the sources got blocked.
+    (
+        first_sand_count.unwrap_or(sand_count),
+        floor_y.map(|_| sand_count),
+    )
+}
+
+/// Where the sand comes from, and how far below the lowest rock the floor is.
+struct SandConfig {
+    sources: Vec<(u16, u16)>,
+    floor_offset: Option<u16>,
+}
+
+impl Default for SandConfig {
+    fn default() -> Self {
+        Self {
+            sources: vec![(500, 0)],
+            floor_offset: Some(2),
         }
     }
 }
 
+/// The grid, with the positions of the sources in it: its columns are shifted so that all the
+/// sand fits.
+struct Layout {
+    grid: Grid,
+    sources: Vec<(u16, u16)>,
+    /// Row of the lowest rock.
+    max_y: u16,
+    floor_y: Option<u16>,
+}
+
+/// Builds a grid wide enough for the sand to pile up on the floor, or to reach the lowest
+/// rock when there is no floor.
+fn build_grid(rocks: &[Vec<(u16, u16)>], config: &SandConfig) -> Result<Layout, &'static str> {
+    let max_y = *rocks
+        .iter()
+        .flatten()
+        .map(|(_, y)| y)
+        .max()
+        .ok_or("No rocks")?;
+    if config.floor_offset == Some(0) {
+        return Err("The floor must be below the lowest rock");
+    }
+    let floor_y = config
+        .floor_offset
+        .map(|offset| max_y.checked_add(offset).ok_or("Cave too deep"))
+        .transpose()?;
+    // The lowest row where sand can be.
+    let bottom = floor_y.map_or(max_y, |y| y - 1);
+    if config.sources.iter().any(|&(_, y)| y > bottom) {
+        return Err("Source below the bottom of the cave");
+    }
+    // Sand spreads by one column on each side for each line it falls, and one more column is
+    // needed on each side for the diagonal checks. Columns are signed, since sand can spread
+    // left of x = 0.
+    let spreads = config
+        .sources
+        .iter()
+        .map(|&(x, y)| (x as i32, (bottom - y) as i32));
+    let rock_columns = rocks.iter().flatten().map(|&(x, _)| x as i32);
+    let min_x_bound = rock_columns
+        .clone()
+        .chain(spreads.clone().map(|(x, spread)| x - spread))
+        .min()
+        .unwrap();
+    let max_x_bound = rock_columns
+        .chain(spreads.map(|(x, spread)| x + spread))
+        .max()
+        .unwrap();
+    let width = u16::try_from(max_x_bound - min_x_bound + 3).map_err(|_| "Cave too wide")?;
+    let height = bottom.checked_add(1).ok_or("Cave too deep")?;
+    let mut grid = Grid::new(height, width);
+    populate_grid(&mut grid, rocks, min_x_bound);
+    let sources = config
+        .sources
+        .iter()
+        .map(|&(x, y)| ((x as i32 - min_x_bound + 1) as u16, y))
+        .collect::<Vec<_>>();
+    if sources.iter().any(|&(x, y)| grid.is_full(x, y)) {
+        return Err("Source inside a rock");
+    }
+    Ok(Layout {
+        grid,
+        sources,
+        max_y,
+        floor_y,
+    })
+}
+
+const USAGE: &str = "Usage: day14 [render [--every <grains>] [--pgm]] [--source <x>,<y>]... \
+                     [--floor <offset> | --floor none]";
+
 fn main() {
+    let mut render = false;
+    let mut every = None;
+    let mut pgm = false;
+    let mut config = SandConfig::default();
+    let mut sources = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect(USAGE);
+        match arg.as_str() {
+            "render" => render = true,
+            "--every" => every = Some(value().parse::<usize>().expect("Invalid number of grains")),
+            "--pgm" => pgm = true,
+            "--source" => sources.push(parse_point(&value())),
+            "--floor" => {
+                config.floor_offset = match value().as_str() {
+                    "none" => None,
+                    offset => match offset.parse() {
+                        Ok(0) => panic!("The floor must be below the lowest rock"),
+                        Ok(offset) => Some(offset),
+                        Err(_) => panic!("Invalid floor offset"),
+                    },
+                }
+            }
+            _ => panic!("{}", USAGE),
+        }
+    }
+    if !sources.is_empty() {
+        config.sources = sources;
+    }
     let rocks = std::io::stdin()
         .lines()
         .map(Result::unwrap)
         .map(parse_line)
         .collect::<Vec<_>>();
-    let max_y = *rocks.iter().flatten().map(|(_, y)| y).max().unwrap();
-    let min_x = *rocks.iter().flatten().map(|(x, _)| x).min().unwrap();
-    let max_x = *rocks.iter().flatten().map(|(x, _)| x).max().unwrap();
-    let min_x_bound = min(min_x, 500 - max_y as u16);
-    let max_x_bound = max(max_x, 500 + max_y as u16);
-    let mut grid = Grid::new(max_y + 2, max_x_bound - min_x_bound + 1);
-    populate_grid(&mut grid, &rocks, min_x_bound);
-    let (part_1, part_2) = fill_sand(&mut grid, 500 - min_x_bound + 1, max_y);
+    let Layout {
+        mut grid,
+        sources,
+        max_y,
+        floor_y,
+    } = build_grid(&rocks, &config).unwrap();
+    let rocks = grid.clone();
+    // Snapshots are taken every `every` grains, or at the end of each part by default.
+    let mut snapshot = |grid: &Grid, event| {
+        let grains = match (event, every) {
+            (SandEvent::Rested(n) | SandEvent::SourceBlocked(n), Some(every)) if n % every == 0 => {
+                n
+            }
+            (SandEvent::ReachedAbyss(n) | SandEvent::SourceBlocked(n), None) => n,
+            _ => return,
+        };
+        let cave = Cave {
+            rocks: &rocks,
+            grid,
+            sources: &sources,
+            floor: floor_y.is_some(),
+        };
+        if pgm {
+            render::write_pgm(&mut std::io::stdout().lock(), &cave).expect("Error writing stdout");
+        } else {
+            println!("After {} grains of sand:\n{}", grains, cave);
+        }
+    };
+    let (part_1, part_2) = if render {
+        fill_sand(&mut grid, &sources, max_y, floor_y, &mut snapshot)
+    } else {
+        fill_sand(&mut grid, &sources, max_y, floor_y, &mut |_, _| {})
+    };
+    if render {
+        return;
+    }
     println!("{}", part_1);
-    println!("{}", part_2);
+    if let Some(part_2) = part_2 {
+        println!("{}", part_2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [&str; 2] = [
+        "498,4 -> 498,6 -> 496,6",
+        "503,4 -> 502,4 -> 502,9 -> 494,9",
+    ];
+
+    fn run(rocks: &[Vec<(u16, u16)>], config: &SandConfig) -> (Layout, (usize, Option<usize>)) {
+        let mut layout = build_grid(rocks, config).unwrap();
+        let counts = fill_sand(
+            &mut layout.grid,
+            &layout.sources,
+            layout.max_y,
+            layout.floor_y,
+            &mut |_, _| {},
+        );
+        (layout, counts)
+    }
+
+    /// Drops every grain from its source, one source after the other.
+    fn reference(rocks: &[Vec<(u16, u16)>], config: &SandConfig) -> (usize, Option<usize>) {
+        let Layout {
+            mut grid,
+            sources,
+            max_y,
+            floor_y,
+        } = build_grid(rocks, config).unwrap();
+        let floor_y = floor_y.unwrap();
+        let mut count = 0;
+        let mut first_count = None;
+        let mut blocked = vec![false; sources.len()];
+        while blocked.contains(&false) {
+            for (i, &(mut x, mut y)) in sources.iter().enumerate() {
+                if blocked[i] || grid.is_full(x, y) {
+                    blocked[i] = true;
+                    continue;
+                }
+                while y + 1 < floor_y {
+                    match [x, x - 1, x + 1]
+                        .into_iter()
+                        .find(|&x| !grid.is_full(x, y + 1))
+                    {
+                        Some(next) => (x, y) = (next, y + 1),
+                        None => break,
+                    }
+                    if y == max_y && first_count.is_none() {
+                        first_count = Some(count);
+                    }
+                }
+                grid.set_full(x, y);
+                count += 1;
+            }
+        }
+        (first_count.unwrap_or(count), Some(count))
+    }
+
+    #[test]
+    fn test_render_milestones() {
+        let rocks = EXAMPLE.map(|l| parse_line(l.to_owned()));
+        let Layout {
+            mut grid,
+            sources,
+            max_y,
+            floor_y,
+        } = build_grid(&rocks, &SandConfig::default()).unwrap();
+        let rocks = grid.clone();
+        let mut snapshots = Vec::new();
+        let counts = fill_sand(&mut grid, &sources, max_y, floor_y, &mut |grid, event| {
+            if !matches!(event, SandEvent::Rested(_)) {
+                let cave = Cave {
+                    rocks: &rocks,
+                    grid,
+                    sources: &sources,
+                    floor: true,
+                };
+                snapshots.push(cave.to_string());
+            }
+        });
+        assert_eq!(counts, (24, Some(93)));
+        let part_1 = snapshots[0]
+            .lines()
+            .map(|l| l.trim_matches('.'))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            &part_1[..10],
+            [
+                "+",
+                "",
+                "o",
+                "ooo",
+                "#ooo##",
+                "o#ooo#",
+                "###ooo#",
+                "oooo#",
+                "o.ooooo#",
+                "#########"
+            ]
+        );
+        assert_eq!(snapshots[1].lines().next(), Some("...........o..........."));
+        assert_eq!(snapshots[1].lines().last(), Some("~~~~~~~~~~~~~~~~~~~~~~~"));
+    }
+
+    #[test]
+    fn test_sources_and_floor() {
+        let rocks = EXAMPLE.map(|l| parse_line(l.to_owned()));
+        let no_floor = SandConfig {
+            floor_offset: None,
+            ..SandConfig::default()
+        };
+        assert_eq!(run(&rocks, &no_floor).1, (24, None));
+        // The source moved one column to the left, with the floor right below the rocks.
+        let config = SandConfig {
+            sources: vec![(499, 2)],
+            floor_offset: Some(1),
+        };
+        assert_eq!(run(&rocks, &config).1, reference(&rocks, &config));
+
+        let config = SandConfig {
+            sources: vec![(500, 0), (497, 1), (505, 3)],
+            floor_offset: Some(4),
+        };
+        let (layout, counts) = run(&rocks, &config);
+        assert_eq!(counts, reference(&rocks, &config));
+        assert_eq!(layout.grid.height, 13);
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        let rocks = EXAMPLE.map(|l| parse_line(l.to_owned()));
+        let config = |source, floor_offset| SandConfig {
+            sources: vec![source],
+            floor_offset,
+        };
+        // The lowest rock is at y = 9.
+        assert!(build_grid(&rocks, &config((505, 9), None)).is_ok());
+        assert!(build_grid(&rocks, &config((500, 10), Some(1))).is_err());
+        assert!(build_grid(&rocks, &config((500, 11), None)).is_err());
+        assert!(build_grid(&rocks, &config((500, 0), Some(0))).is_err());
+        assert!(build_grid(&rocks, &config((498, 5), None)).is_err());
+        assert!(build_grid(&rocks, &config((494, 9), Some(2))).is_err());
+        assert_eq!(run(&rocks, &config((505, 9), None)).1, (0, None));
+        // A cup catching all the sand.
+        let cup = vec![parse_line("497,1 -> 497,4 -> 503,4 -> 503,1".to_owned())];
+        assert_eq!(run(&cup, &config((500, 2), None)).1, (4, None));
+
+        let rocks = vec![parse_line("500,65534 -> 501,65534".to_owned())];
+        let too_deep = Some("Cave too deep");
+        assert_eq!(
+            build_grid(&rocks, &config((500, 0), Some(2))).err(),
+            too_deep
+        );
+        assert!(build_grid(&rocks, &config((500, 65533), Some(1))).is_ok());
+        let rocks = vec![parse_line("500,65535 -> 501,65535".to_owned())];
+        assert_eq!(
+            build_grid(&rocks, &config((500, 65535), None)).err(),
+            too_deep
+        );
+    }
+
+    #[test]
+    fn test_source_near_the_left_edge() {
+        let rocks = vec![parse_line("0,5 -> 6,5".to_owned())];
+        let config = SandConfig {
+            sources: vec![(3, 0)],
+            ..SandConfig::default()
+        };
+        let (layout, counts) = run(&rocks, &config);
+        assert_eq!(counts, reference(&rocks, &config));
+        // Sand spreads 6 columns to the left of the source, down to x = -3.
+        assert_eq!(layout.sources, [(7, 0)]);
+    }
+
+    #[test]
+    fn test_deep_cave() {
+        let rocks = vec![parse_line("490,300 -> 510,300".to_owned())];
+        let (layout, counts) = run(&rocks, &SandConfig::default());
+        assert_eq!(layout.max_y, 300);
+        assert_eq!(counts, reference(&rocks, &SandConfig::default()));
+    }
 }
@@ -0,0 +1,92 @@
+//! Draws the cave as ASCII art or as a PGM image.
+
+use crate::Grid;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Air,
+    Rock,
+    Sand,
+    Source,
+    Floor,
+}
+
+impl Tile {
+    fn ascii(self) -> char {
+        match self {
+            Tile::Air => '.',
+            Tile::Rock => '#',
+            Tile::Sand => 'o',
+            Tile::Source => '+',
+            Tile::Floor => '~',
+        }
+    }
+
+    fn grey(self) -> u8 {
+        match self {
+            Tile::Air => 0,
+            Tile::Rock => 96,
+            Tile::Sand => 208,
+            Tile::Source => 255,
+            Tile::Floor => 48,
+        }
+    }
+}
+
+/// The bit-packed grids don't tell rock from sand, so the cave keeps a copy of the grid as it
+/// was before any sand fell.
+pub struct Cave<'a> {
+    pub rocks: &'a Grid,
+    pub grid: &'a Grid,
+    pub sources: &'a [(u16, u16)],
+    pub floor: bool,
+}
+
+impl Cave<'_> {
+    fn tile(&self, x: u16, y: u16) -> Tile {
+        if self.rocks.is_full(x, y) {
+            Tile::Rock
+        } else if self.grid.is_full(x, y) {
+            Tile::Sand
+        } else if self.sources.contains(&(x, y)) {
+            Tile::Source
+        } else {
+            Tile::Air
+        }
+    }
+
+    /// Rows of the grid, followed by the floor if there is one.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<Tile>> + '_ {
+        (0..self.grid.height)
+            .map(|y| (0..self.grid.width).map(|x| self.tile(x, y)).collect())
+            .chain(
+                self.floor
+                    .then(|| vec![Tile::Floor; self.grid.width as usize]),
+            )
+    }
+}
+
+impl std::fmt::Display for Cave<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.rows() {
+            writeln!(
+                f,
+                "{}",
+                row.into_iter().map(Tile::ascii).collect::<String>()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write_pgm<W: std::io::Write>(out: &mut W, cave: &Cave) -> std::io::Result<()> {
+    let rows = cave.rows().collect::<Vec<_>>();
+    writeln!(out, "P5\n{} {}\n255", cave.grid.width, rows.len())?;
+    out.write_all(
+        &rows
+            .into_iter()
+            .flatten()
+            .map(Tile::grey)
+            .collect::<Vec<_>>(),
+    )
+}